@@ -0,0 +1,195 @@
+//! Derive macros for `frclib-commands`, use them through the `derive` feature of that crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Ident, LitStr};
+
+/// Implements `Subsystem` for a struct.
+///
+/// The subsystem is constructed with `Default::default()` unless `construct` names an associated function,
+/// the other attributes name methods that the matching `Subsystem` method forwards to.
+///
+/// ```
+/// use frclib_commands::{Command, CommandManager, Subsystem, SubsystemCell};
+/// use std::time::Duration;
+///
+/// #[derive(Default, Subsystem)]
+/// #[subsystem(name = "drivetrain", default_command = idle, periodic = update_odometry)]
+/// struct Drivetrain;
+/// impl Drivetrain {
+///     fn idle(&mut self) -> Command {
+///         self.run_command(|_period| println!("Idling"))
+///     }
+///
+///     fn update_odometry(&self, _period: Duration) {}
+/// }
+///
+/// let mut manager = CommandManager::new();
+/// let drivetrain = SubsystemCell::<Drivetrain>::generate(&mut manager);
+/// drivetrain.run_once_command(|| println!("Stopping")).schedule();
+/// manager.run();
+/// ```
+///
+/// - `name = "..."`: the name of the subsystem, defaults to the type name.
+/// - `construct = fn`: an associated `fn() -> Self` used to construct the subsystem.
+/// - `default_command = method`: a `&mut self` method returning a `Command` or `Option<Command>`.
+/// - `periodic = method`: a `&self` method taking the time since the last cycle.
+/// - `log = method`: a `&self` method called after `periodic`.
+/// - `no_helpers`: skips generating the command helpers.
+///
+/// The struct also implements `SubsystemRequirement` so it can be passed to `with_subsystem` directly,
+/// and gets `command_builder`, `run_command`, `run_once_command` and `start_end_command` helpers
+/// that build commands requiring it.
+#[proc_macro_derive(Subsystem, attributes(subsystem))]
+pub fn derive_subsystem(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_subsystem(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct SubsystemArgs {
+    name: Option<LitStr>,
+    construct: Option<Ident>,
+    default_command: Option<Ident>,
+    periodic: Option<Ident>,
+    log: Option<Ident>,
+    no_helpers: bool,
+}
+
+impl SubsystemArgs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut args = Self::default();
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("subsystem"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    args.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("construct") {
+                    args.construct = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default_command") {
+                    args.default_command = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("periodic") {
+                    args.periodic = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("log") {
+                    args.log = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("no_helpers") {
+                    args.no_helpers = true;
+                } else {
+                    return Err(meta.error("unknown subsystem attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(args)
+    }
+}
+
+fn expand_subsystem(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let args = SubsystemArgs::parse(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let name = args.name.map(|name| {
+        quote! {
+            fn name(&self) -> &'static str {
+                #name
+            }
+        }
+    });
+    let construct = args.construct.map_or_else(
+        || quote!(::core::default::Default::default()),
+        |construct| quote!(Self::#construct()),
+    );
+    let default_command = args.default_command.map(|method| {
+        quote! {
+            fn default_command(&mut self) -> ::core::option::Option<::frclib_commands::Command> {
+                ::core::convert::Into::into(Self::#method(self))
+            }
+        }
+    });
+    let periodic = args.periodic.map(|method| {
+        quote! {
+            fn periodic(&self, period: ::std::time::Duration) {
+                Self::#method(self, period);
+            }
+        }
+    });
+    let log = args.log.map(|method| {
+        quote! {
+            fn log(&self) {
+                Self::#method(self);
+            }
+        }
+    });
+    let helpers = (!args.no_helpers).then(|| {
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// A command builder that requires this subsystem.
+                #[must_use]
+                pub fn command_builder(&self) -> ::frclib_commands::CommandBuilder {
+                    ::frclib_commands::CommandBuilder::new().with_subsystem(self)
+                }
+
+                /// A command requiring this subsystem that calls `periodic` every cycle and never finishes.
+                #[must_use]
+                pub fn run_command(
+                    &self,
+                    periodic: impl FnMut(::std::time::Duration) + 'static,
+                ) -> ::frclib_commands::Command {
+                    self.command_builder().periodic(periodic).build()
+                }
+
+                /// A command requiring this subsystem that calls `init` once and finishes.
+                #[must_use]
+                pub fn run_once_command(
+                    &self,
+                    init: impl FnMut() + 'static,
+                ) -> ::frclib_commands::Command {
+                    self.command_builder().init(init).is_finished(|| true).build()
+                }
+
+                /// A command requiring this subsystem that calls `start` when scheduled
+                /// and `end` when it is interrupted, it never finishes on its own.
+                #[must_use]
+                pub fn start_end_command(
+                    &self,
+                    start: impl FnMut() + 'static,
+                    mut end: impl FnMut() + 'static,
+                ) -> ::frclib_commands::Command {
+                    self.command_builder()
+                        .init(start)
+                        .end(move |_interrupted| end())
+                        .build()
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::frclib_commands::Subsystem for #ident #ty_generics #where_clause {
+            #name
+
+            fn construct() -> Self {
+                #construct
+            }
+
+            #default_command
+            #periodic
+            #log
+        }
+
+        impl #impl_generics ::frclib_commands::SubsystemRequirement for #ident #ty_generics #where_clause {
+            fn suid(&self) -> ::frclib_commands::SubsystemSUID {
+                ::frclib_commands::Subsystem::suid(self)
+            }
+        }
+
+        #helpers
+    })
+}
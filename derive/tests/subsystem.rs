@@ -0,0 +1,91 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use frclib_commands::{
+    Command, CommandManager, CommandTrait, ScheduleOutcome, Subsystem, SubsystemCell,
+};
+
+thread_local! {
+    static PERIODIC_RUNS: Cell<usize> = const { Cell::new(0) };
+}
+
+#[derive(Default, Subsystem)]
+#[subsystem(name = "drivetrain", default_command = idle, periodic = update_odometry)]
+struct Drivetrain {
+    speed: Rc<Cell<f64>>,
+}
+impl Drivetrain {
+    fn idle(&mut self) -> Command {
+        let speed = self.speed.clone();
+        self.run_command(move |_period| speed.set(0.0))
+    }
+
+    fn update_odometry(&self, _period: Duration) {
+        PERIODIC_RUNS.with(|runs| runs.set(runs.get() + 1));
+    }
+}
+
+// helpers are suffixed (`run_command`, `command_builder`, ...) so they don't clash with the subsystem's own methods
+#[derive(Subsystem)]
+#[subsystem(construct = new)]
+struct Intake {
+    deployed: bool,
+}
+impl Intake {
+    const fn new() -> Self {
+        Self { deployed: true }
+    }
+
+    #[allow(dead_code)]
+    fn run(&self) {}
+}
+
+#[derive(Default, Subsystem)]
+#[subsystem(no_helpers)]
+struct Climber;
+impl Climber {
+    #[allow(dead_code)]
+    fn run_command(&self) {}
+}
+
+#[test]
+fn derived_subsystems() {
+    let mut manager = CommandManager::new();
+    let drivetrain = SubsystemCell::<Drivetrain>::generate(&mut manager);
+    let intake = SubsystemCell::<Intake>::generate(&mut manager);
+    assert_eq!(drivetrain.name(), "drivetrain");
+    assert!(intake.name().ends_with("Intake"));
+    assert!(intake.deployed);
+
+    drivetrain.speed.set(1.0);
+    manager.run();
+    assert_eq!(drivetrain.speed.get(), 0.0);
+    assert_eq!(PERIODIC_RUNS.with(Cell::get), 1);
+
+    // helper commands require the subsystem and interrupt its default command
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let (started, ended) = (log.clone(), log.clone());
+    let drive = drivetrain.start_end_command(
+        move || started.borrow_mut().push("start"),
+        move || ended.borrow_mut().push("end"),
+    );
+    assert_eq!(drive.get_requirements(), [drivetrain.suid()]);
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(drive) else {
+        panic!("The drivetrain's default command yields");
+    };
+    drivetrain.speed.set(1.0);
+    manager.run();
+    assert_eq!(drivetrain.speed.get(), 1.0);
+    assert!(manager.cancel(index));
+    manager.run();
+    assert_eq!(*log.borrow(), ["start", "end"]);
+
+    let speed = drivetrain.speed.clone();
+    let stop = drivetrain.run_once_command(move || speed.set(-1.0));
+    assert!(manager.schedule(stop).is_scheduled());
+    manager.run();
+    assert_eq!(drivetrain.speed.get(), -1.0);
+}
@@ -0,0 +1,457 @@
+//! Behavior trees that run as a single [`Command`].
+//!
+//! Every node reports a [`Status`] each time it is ticked, unlike [`CommandTrait::is_finished`]
+//! this lets a node fail so sequences can stop early and fallbacks can try something else.
+//! Commands are used as leaves, a command succeeds when it finishes and fails when it
+//! returns an [error](CommandTrait::take_error).
+//! The tree adopts the requirements of every command in it and is scheduled like any other command.
+//!
+//! # Examples
+//! ```
+//! use frclib_commands::{behavior_tree::Node, CommandBuilder, CommandManager};
+//!
+//! let mut manager = CommandManager::new();
+//! let has_note = || false;
+//!
+//! Node::sequence(vec![
+//!     Node::fallback(vec![
+//!         Node::condition(has_note),
+//!         CommandBuilder::new().is_finished(|| true).build().into(),
+//!     ]),
+//!     CommandBuilder::new()
+//!         .try_init(|| Err("no target in sight"))
+//!         .build()
+//!         .into(),
+//! ])
+//! .retry(3)
+//! .into_command()
+//! .schedule();
+//!
+//! manager.run();
+//! ```
+
+use std::{fmt::Debug, time::Duration};
+
+use thiserror::Error;
+
+use crate::{
+    commands::{
+        group_interruption_behavior, group_priority, group_requirements, CommandError,
+        CommandTrait, InterruptionBehavior,
+    },
+    conditions::BooleanSupplier,
+    Command, SubsystemSUID,
+};
+
+/// The result of ticking a [`Node`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// The error a [`BehaviorTree`] command ends with when its root node fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Behavior tree failed")]
+pub struct TreeFailure;
+
+enum Decorator {
+    Invert,
+    ForceSuccess,
+    ForceFailure,
+    Repeat { times: usize, count: usize },
+    Retry { times: usize, count: usize },
+}
+
+enum NodeKind {
+    Action(Command),
+    Condition(Box<dyn BooleanSupplier>),
+    Leaf(Box<dyn FnMut(Duration) -> Status>),
+    Sequence { children: Vec<Node>, current: usize },
+    Fallback { children: Vec<Node>, current: usize },
+    Selector(Vec<Node>),
+    Decorator(Decorator, Box<Node>),
+}
+
+/// A node of a behavior tree.
+///
+/// A node that returned [`Status::Running`] and is not ticked again is halted,
+/// which interrupts any command still running under it.
+pub struct Node {
+    kind: NodeKind,
+    running: bool,
+}
+impl Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = match &self.kind {
+            NodeKind::Action(command) => {
+                let mut debug = f.debug_struct("Action");
+                debug.field("command", command);
+                debug
+            }
+            NodeKind::Condition(_) => f.debug_struct("Condition"),
+            NodeKind::Leaf(_) => f.debug_struct("Leaf"),
+            NodeKind::Sequence { children, .. } => {
+                let mut debug = f.debug_struct("Sequence");
+                debug.field("children", children);
+                debug
+            }
+            NodeKind::Fallback { children, .. } => {
+                let mut debug = f.debug_struct("Fallback");
+                debug.field("children", children);
+                debug
+            }
+            NodeKind::Selector(children) => {
+                let mut debug = f.debug_struct("Selector");
+                debug.field("children", children);
+                debug
+            }
+            NodeKind::Decorator(_, child) => {
+                let mut debug = f.debug_struct("Decorator");
+                debug.field("child", child);
+                debug
+            }
+        };
+        debug.field("running", &self.running).finish()
+    }
+}
+impl Node {
+    const fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            running: false,
+        }
+    }
+
+    /// A leaf that runs a command, succeeding when it finishes and failing if it returns an error.
+    #[must_use]
+    pub const fn action(command: Command) -> Self {
+        Self::new(NodeKind::Action(command))
+    }
+
+    /// A leaf that succeeds if `condition` is true and fails otherwise, it never runs.
+    #[must_use]
+    pub fn condition(condition: impl BooleanSupplier + 'static) -> Self {
+        Self::new(NodeKind::Condition(Box::new(condition)))
+    }
+
+    /// A leaf that calls `tick` with the time since the last cycle until it stops returning [`Status::Running`].
+    #[must_use]
+    pub fn leaf(tick: impl FnMut(Duration) -> Status + 'static) -> Self {
+        Self::new(NodeKind::Leaf(Box::new(tick)))
+    }
+
+    /// Runs `children` in order, failing as soon as one fails and succeeding once all have succeeded.
+    #[must_use]
+    pub const fn sequence(children: Vec<Self>) -> Self {
+        Self::new(NodeKind::Sequence {
+            children,
+            current: 0,
+        })
+    }
+
+    /// Runs `children` in order, succeeding as soon as one succeeds and failing once all have failed.
+    #[must_use]
+    pub const fn fallback(children: Vec<Self>) -> Self {
+        Self::new(NodeKind::Fallback {
+            children,
+            current: 0,
+        })
+    }
+
+    /// A reactive fallback, `children` are ticked from the first one every cycle
+    /// so a higher priority child taking over halts the one that was running.
+    #[must_use]
+    pub const fn selector(children: Vec<Self>) -> Self {
+        Self::new(NodeKind::Selector(children))
+    }
+
+    /// Swaps the success and failure of this node.
+    #[must_use]
+    pub fn invert(self) -> Self {
+        Self::new(NodeKind::Decorator(Decorator::Invert, Box::new(self)))
+    }
+
+    /// Succeeds once this node is done, whether it succeeded or failed.
+    #[must_use]
+    pub fn force_success(self) -> Self {
+        Self::new(NodeKind::Decorator(Decorator::ForceSuccess, Box::new(self)))
+    }
+
+    /// Fails once this node is done, whether it succeeded or failed.
+    #[must_use]
+    pub fn force_failure(self) -> Self {
+        Self::new(NodeKind::Decorator(Decorator::ForceFailure, Box::new(self)))
+    }
+
+    /// Runs this node again after it succeeds until it has succeeded `times` times,
+    /// failing as soon as it fails.
+    #[must_use]
+    pub fn repeat(self, times: usize) -> Self {
+        Self::new(NodeKind::Decorator(
+            Decorator::Repeat { times, count: 0 },
+            Box::new(self),
+        ))
+    }
+
+    /// Runs this node again after it fails until it has failed `times` times,
+    /// succeeding as soon as it succeeds.
+    #[must_use]
+    pub fn retry(self, times: usize) -> Self {
+        Self::new(NodeKind::Decorator(
+            Decorator::Retry { times, count: 0 },
+            Box::new(self),
+        ))
+    }
+
+    /// Wraps this node in a [`BehaviorTree`] command.
+    pub fn into_command(self) -> Command {
+        BehaviorTree::new(self).into()
+    }
+
+    /// Ticks this node once, the first tick after it stopped running starts it over.
+    pub fn tick(&mut self, period: Duration) -> Status {
+        let running = self.running;
+        let status = match &mut self.kind {
+            NodeKind::Action(command) => tick_command(command, running, period),
+            NodeKind::Condition(condition) => {
+                if condition.get_as_boolean() {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            NodeKind::Leaf(tick) => tick(period),
+            NodeKind::Sequence { children, current } => {
+                tick_in_order(children, current, running, period, Status::Success)
+            }
+            NodeKind::Fallback { children, current } => {
+                tick_in_order(children, current, running, period, Status::Failure)
+            }
+            NodeKind::Selector(children) => {
+                let mut result = (Status::Failure, children.len());
+                for (i, child) in children.iter_mut().enumerate() {
+                    let status = child.tick(period);
+                    if status != Status::Failure {
+                        result = (status, i);
+                        break;
+                    }
+                }
+                for child in children.iter_mut().skip(result.1 + 1) {
+                    child.halt();
+                }
+                result.0
+            }
+            NodeKind::Decorator(decorator, child) => {
+                tick_decorator(decorator, child, running, period)
+            }
+        };
+        self.running = status == Status::Running;
+        status
+    }
+
+    /// Stops this node if it is running, interrupting any command running under it.
+    pub fn halt(&mut self) {
+        if !self.running {
+            return;
+        }
+        self.running = false;
+        match &mut self.kind {
+            NodeKind::Action(command) => command.end(true),
+            NodeKind::Condition(_) | NodeKind::Leaf(_) => {}
+            NodeKind::Sequence { children, current } | NodeKind::Fallback { children, current } => {
+                if let Some(child) = children.get_mut(*current) {
+                    child.halt();
+                }
+                *current = 0;
+            }
+            NodeKind::Selector(children) => children.iter_mut().for_each(Self::halt),
+            NodeKind::Decorator(_, child) => child.halt(),
+        }
+    }
+
+    fn commands(&self) -> Vec<&Command> {
+        match &self.kind {
+            NodeKind::Action(command) => vec![command],
+            NodeKind::Condition(_) | NodeKind::Leaf(_) => Vec::new(),
+            NodeKind::Sequence { children, .. }
+            | NodeKind::Fallback { children, .. }
+            | NodeKind::Selector(children) => children.iter().flat_map(Self::commands).collect(),
+            NodeKind::Decorator(_, child) => child.commands(),
+        }
+    }
+}
+impl From<Command> for Node {
+    fn from(command: Command) -> Self {
+        Self::action(command)
+    }
+}
+
+fn tick_command(command: &mut Command, running: bool, period: Duration) -> Status {
+    if !running {
+        command.init();
+    }
+    let mut error = command.take_error();
+    if error.is_none() {
+        command.periodic(period);
+        error = command.take_error();
+    }
+    if let Some(error) = error {
+        tracing::debug!("{} failed: {error}", command.get_name());
+        command.end(true);
+        Status::Failure
+    } else if command.is_finished() {
+        command.end(false);
+        Status::Success
+    } else {
+        Status::Running
+    }
+}
+
+/// Ticks `children` from `current` until one returns something other than `skip`.
+fn tick_in_order(
+    children: &mut [Node],
+    current: &mut usize,
+    running: bool,
+    period: Duration,
+    skip: Status,
+) -> Status {
+    if !running {
+        *current = 0;
+    }
+    while let Some(child) = children.get_mut(*current) {
+        let status = child.tick(period);
+        if status != skip {
+            if status != Status::Running {
+                *current = 0;
+            }
+            return status;
+        }
+        *current += 1;
+    }
+    *current = 0;
+    skip
+}
+
+fn tick_decorator(
+    decorator: &mut Decorator,
+    child: &mut Node,
+    running: bool,
+    period: Duration,
+) -> Status {
+    let status = child.tick(period);
+    match decorator {
+        Decorator::Invert => match status {
+            Status::Success => Status::Failure,
+            Status::Failure => Status::Success,
+            Status::Running => Status::Running,
+        },
+        Decorator::ForceSuccess if status != Status::Running => Status::Success,
+        Decorator::ForceFailure if status != Status::Running => Status::Failure,
+        Decorator::ForceSuccess | Decorator::ForceFailure => Status::Running,
+        Decorator::Repeat { times, count } => {
+            repeat_until(status, Status::Success, *times, count, running)
+        }
+        Decorator::Retry { times, count } => {
+            repeat_until(status, Status::Failure, *times, count, running)
+        }
+    }
+}
+
+/// Counts `status` equal to `repeated` until it happened `times` times, the child is ticked again next cycle.
+fn repeat_until(
+    status: Status,
+    repeated: Status,
+    times: usize,
+    count: &mut usize,
+    running: bool,
+) -> Status {
+    if !running {
+        *count = 0;
+    }
+    if status != repeated {
+        *count = 0;
+        return status;
+    }
+    *count += 1;
+    if *count >= times {
+        *count = 0;
+        status
+    } else {
+        Status::Running
+    }
+}
+
+/// A command that ticks a behavior tree once per cycle until its root succeeds or fails,
+/// a failed tree ends with a [`TreeFailure`] error.
+#[derive(Debug)]
+pub struct BehaviorTree {
+    root: Node,
+    status: Status,
+    error: Option<CommandError>,
+}
+impl BehaviorTree {
+    #[must_use]
+    pub const fn new(root: Node) -> Self {
+        Self {
+            root,
+            status: Status::Running,
+            error: None,
+        }
+    }
+}
+impl CommandTrait for BehaviorTree {
+    fn init(&mut self) {
+        self.status = Status::Running;
+        self.error = None;
+    }
+
+    fn periodic(&mut self, period: Duration) {
+        self.status = self.root.tick(period);
+        if self.status == Status::Failure {
+            self.error = Some(Box::new(TreeFailure));
+        }
+    }
+
+    fn end(&mut self, _interrupted: bool) {
+        self.root.halt();
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.status == Status::Success
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.root
+            .commands()
+            .into_iter()
+            .all(CommandTrait::run_when_disabled)
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        group_interruption_behavior(self.root.commands())
+    }
+
+    fn priority(&self) -> i32 {
+        group_priority(self.root.commands())
+    }
+
+    fn get_requirements(&self) -> Vec<SubsystemSUID> {
+        group_requirements(self.root.commands())
+    }
+
+    fn get_name(&self) -> String {
+        String::from("Behavior Tree")
+    }
+}
+impl From<BehaviorTree> for Command {
+    fn from(tree: BehaviorTree) -> Self {
+        Self::Custom(Box::new(tree))
+    }
+}
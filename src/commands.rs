@@ -1,9 +1,46 @@
-use std::{collections::HashSet, fmt::Debug, time::Duration};
-
-use crate::{SubsystemRequirement, SubsystemSUID};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    rc::Rc,
+    time::Duration,
+};
+
+use fxhash::FxHashMap;
+
+use thiserror::Error;
+
+use crate::{
+    conditions::BooleanSupplier,
+    snapshot::{CommandKind, CommandNode, ParallelKind},
+    SubsystemRequirement, SubsystemSUID,
+};
 pub type Requirement<'a> = &'a dyn SubsystemRequirement;
 pub type Requirements<'a, 'b> = &'a [Requirement<'b>];
 
+/// An error that ended a fallible command, see [`CommandTrait::take_error`].
+pub type CommandError = Box<dyn std::error::Error>;
+type InitFn = Box<dyn FnMut() -> Result<(), CommandError>>;
+type PeriodicFn = Box<dyn FnMut(Duration) -> Result<(), CommandError>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CompositionError {
+    #[error("Multiple commands in a parallel group cannot require the same subsystem ({0:#x})")]
+    OverlappingRequirements(SubsystemSUID),
+}
+
+/// What happens when a command of equal priority is scheduled that requires a subsystem this command is using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterruptionBehavior {
+    /// This command is interrupted and the incoming command is scheduled.
+    #[default]
+    CancelSelf,
+    /// The incoming command is rejected and this command keeps running.
+    CancelIncoming,
+}
+
 pub trait CommandTrait {
     /// Called when the command is first scheduled.
     fn init(&mut self) {}
@@ -35,10 +72,40 @@ pub trait CommandTrait {
         false
     }
 
+    /// How this command reacts to an incoming command of equal priority,
+    /// defaults to [`InterruptionBehavior::CancelIncoming`] if [`cancel_incoming`](CommandTrait::cancel_incoming) is true.
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        if self.cancel_incoming() {
+            InterruptionBehavior::CancelIncoming
+        } else {
+            InterruptionBehavior::CancelSelf
+        }
+    }
+
+    /// The priority of this command, an incoming command with a higher priority
+    /// always interrupts this command while one with a lower priority is always rejected.
+    fn priority(&self) -> i32 {
+        0
+    }
+
     /// Returns the name of this command.
     fn get_name(&self) -> String {
         String::from("Unnamed Command")
     }
+
+    /// Returns the error raised by the last call to [`init`](CommandTrait::init) or [`periodic`](CommandTrait::periodic), if any.
+    ///
+    /// A command that returns an error is interrupted right away,
+    /// groups return the first error of their members and abort.
+    fn take_error(&mut self) -> Option<CommandError> {
+        None
+    }
+
+    /// The command this command wraps without changing its structure, if any,
+    /// so [`Command::tree`] can show the wrapped command instead of an opaque node.
+    fn wrapped_command(&self) -> Option<&Command> {
+        None
+    }
 }
 
 /// A builder for creating commands.
@@ -64,8 +131,8 @@ pub trait CommandTrait {
 ///     manager.run();
 /// }
 pub struct CommandBuilder {
-    init: Option<Box<dyn FnMut()>>,
-    periodic: Option<Box<dyn FnMut(Duration)>>,
+    init: Option<InitFn>,
+    periodic: Option<PeriodicFn>,
     end: Option<Box<dyn FnMut(bool)>>,
     is_finished: Option<Box<dyn FnMut() -> bool>>,
     requirements: Vec<SubsystemSUID>,
@@ -98,16 +165,46 @@ impl CommandBuilder {
     /// Defines the `init` function for this command.
     /// This is a chainable function for ease of use.
     #[must_use]
-    pub fn init(mut self, init: impl FnMut() + 'static) -> Self {
-        self.init = Some(Box::new(init));
+    pub fn init(mut self, mut init: impl FnMut() + 'static) -> Self {
+        self.init = Some(Box::new(move || {
+            init();
+            Ok(())
+        }));
+        self
+    }
+
+    /// Defines a fallible `init` function for this command,
+    /// returning an error ends the command, see [`CommandTrait::take_error`].
+    /// This is a chainable function for ease of use.
+    #[must_use]
+    pub fn try_init<E: Into<CommandError>>(
+        mut self,
+        mut init: impl FnMut() -> Result<(), E> + 'static,
+    ) -> Self {
+        self.init = Some(Box::new(move || init().map_err(Into::into)));
         self
     }
 
     /// Defines the `periodic` function for this command.
     /// This is a chainable function for ease of use.
     #[must_use]
-    pub fn periodic(mut self, periodic: impl FnMut(Duration) + 'static) -> Self {
-        self.periodic = Some(Box::new(periodic));
+    pub fn periodic(mut self, mut periodic: impl FnMut(Duration) + 'static) -> Self {
+        self.periodic = Some(Box::new(move |period| {
+            periodic(period);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Defines a fallible `periodic` function for this command,
+    /// returning an error ends the command, see [`CommandTrait::take_error`].
+    /// This is a chainable function for ease of use.
+    #[must_use]
+    pub fn try_periodic<E: Into<CommandError>>(
+        mut self,
+        mut periodic: impl FnMut(Duration) -> Result<(), E> + 'static,
+    ) -> Self {
+        self.periodic = Some(Box::new(move |period| periodic(period).map_err(Into::into)));
         self
     }
 
@@ -149,10 +246,17 @@ impl CommandBuilder {
             end: self.end,
             is_finished: self.is_finished,
             requirements: self.requirements,
+            error: None,
         })
     }
 }
 
+impl Default for CommandBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// CommandBuilder methods for creating commands with different combinations of functions.
 impl CommandBuilder {
     pub fn init_only(init: impl FnMut() + 'static, subsystems: Requirements) -> Command {
@@ -279,22 +383,23 @@ impl CommandBuilder {
 }
 
 pub struct SimpleCommand {
-    init: Option<Box<dyn FnMut()>>,
-    periodic: Option<Box<dyn FnMut(Duration)>>,
+    init: Option<InitFn>,
+    periodic: Option<PeriodicFn>,
     end: Option<Box<dyn FnMut(bool)>>,
     is_finished: Option<Box<dyn FnMut() -> bool>>,
     requirements: Vec<SubsystemSUID>,
+    error: Option<CommandError>,
 }
 impl CommandTrait for SimpleCommand {
     fn init(&mut self) {
         if let Some(init) = self.init.as_mut() {
-            init();
+            self.error = init().err();
         }
     }
 
     fn periodic(&mut self, period: Duration) {
         if let Some(periodic) = self.periodic.as_mut() {
-            periodic(period);
+            self.error = periodic(period).err();
         }
     }
 
@@ -307,12 +412,16 @@ impl CommandTrait for SimpleCommand {
     fn is_finished(&mut self) -> bool {
         self.is_finished
             .as_mut()
-            .map_or(false, |is_finished| is_finished())
+            .is_some_and(|is_finished| is_finished())
     }
 
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.requirements.clone()
     }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
 }
 impl Debug for SimpleCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -356,7 +465,7 @@ impl CommandTrait for ConstCommand {
     fn is_finished(&mut self) -> bool {
         self.is_finished
             .as_mut()
-            .map_or(false, |is_finished| is_finished())
+            .is_some_and(|is_finished| is_finished())
     }
 
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
@@ -380,12 +489,20 @@ pub struct ParallelCommand {
     commands: Vec<Command>,
     finished: Vec<bool>,
     requirements: HashSet<SubsystemSUID>,
-    race: bool,
+    kind: ParallelKind,
+    error: Option<CommandError>,
 }
 impl CommandTrait for ParallelCommand {
     fn init(&mut self) {
-        for command in &mut self.commands {
+        self.finished.fill(false);
+        for (i, command) in self.commands.iter_mut().enumerate() {
             command.init();
+            if let Some(error) = command.take_error() {
+                self.error = Some(error);
+                // members that never started are not ended when the group is interrupted
+                self.finished[i + 1..].fill(true);
+                return;
+            }
         }
     }
 
@@ -393,6 +510,10 @@ impl CommandTrait for ParallelCommand {
         for (i, command) in self.commands.iter_mut().enumerate() {
             if !self.finished[i] {
                 command.periodic(period);
+                if let Some(error) = command.take_error() {
+                    self.error = Some(error);
+                    return;
+                }
                 if command.is_finished() {
                     command.end(false);
                     self.finished[i] = true;
@@ -401,25 +522,40 @@ impl CommandTrait for ParallelCommand {
         }
     }
 
-    fn end(&mut self, interrupted: bool) {
-        if interrupted {
-            for (i, command) in self.commands.iter_mut().enumerate() {
-                if !self.finished[i] {
-                    command.end(true);
-                    self.finished[i] = true;
-                }
+    fn end(&mut self, _interrupted: bool) {
+        // members still running when a race or deadline finishes are interrupted too
+        for (i, command) in self.commands.iter_mut().enumerate() {
+            if !self.finished[i] {
+                command.end(true);
+                self.finished[i] = true;
             }
         }
     }
 
     fn is_finished(&mut self) -> bool {
-        if self.race {
-            self.finished.iter().any(|&finished| finished)
-        } else {
-            self.finished.iter().all(|&finished| finished)
+        match self.kind {
+            ParallelKind::All => self.finished.iter().all(|&finished| finished),
+            ParallelKind::Race => self.finished.iter().any(|&finished| finished),
+            ParallelKind::Deadline => self.finished.first().copied().unwrap_or(true),
         }
     }
 
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.commands.iter().all(CommandTrait::run_when_disabled)
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        group_interruption_behavior(&self.commands)
+    }
+
+    fn priority(&self) -> i32 {
+        group_priority(&self.commands)
+    }
+
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.requirements.clone().into_iter().collect()
     }
@@ -438,6 +574,7 @@ pub struct SequentialCommand {
     commands: Vec<Command>,
     current: usize,
     requirements: HashSet<SubsystemSUID>,
+    error: Option<CommandError>,
 }
 impl SequentialCommand {
     fn is_empty(&self) -> bool {
@@ -451,6 +588,7 @@ impl CommandTrait for SequentialCommand {
         }
         self.current = 0;
         self.commands[0].init();
+        self.error = self.commands[0].take_error();
     }
 
     fn periodic(&mut self, period: Duration) {
@@ -458,11 +596,13 @@ impl CommandTrait for SequentialCommand {
             return;
         }
         self.commands[self.current].periodic(period);
-        if self.commands[self.current].is_finished() {
+        self.error = self.commands[self.current].take_error();
+        if self.error.is_none() && self.commands[self.current].is_finished() {
             self.commands[self.current].end(false);
             self.current += 1;
             if self.current < self.commands.len() {
                 self.commands[self.current].init();
+                self.error = self.commands[self.current].take_error();
             }
         }
     }
@@ -479,6 +619,22 @@ impl CommandTrait for SequentialCommand {
         self.current >= self.commands.len()
     }
 
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.commands.iter().all(CommandTrait::run_when_disabled)
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        group_interruption_behavior(&self.commands)
+    }
+
+    fn priority(&self) -> i32 {
+        group_priority(&self.commands)
+    }
+
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.requirements.clone().into_iter().collect()
     }
@@ -523,6 +679,12 @@ impl CommandTrait for ProxyCommand {
         self.get_command().is_finished()
     }
 
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.command
+            .as_mut()
+            .and_then(|command| command.take_error())
+    }
+
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.requirements.iter().copied().collect()
     }
@@ -549,6 +711,468 @@ impl Debug for ProxyCommand {
         Ok(())
     }
 }
+/// A shared slot the output of a [`ValueCommand`] is written to.
+#[derive(Debug)]
+pub struct ValueHandle<T>(Rc<RefCell<Option<T>>>);
+impl<T> Clone for ValueHandle<T> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+impl<T> ValueHandle<T> {
+    /// Takes the output of the last run, `None` if it was interrupted or has not finished yet.
+    #[must_use]
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    fn set(&self, value: T) {
+        self.0.borrow_mut().replace(value);
+    }
+}
+
+/// A command that produces a value of type `T` when it finishes without being interrupted.
+///
+/// # Examples
+/// ```
+/// use frclib_commands::{Command, CommandBuilder, CommandManager, ValueCommand};
+///
+/// let mut manager = CommandManager::new();
+/// let mut samples = vec![0.2, 0.1];
+/// ValueCommand::poll(move || samples.pop(), &[])
+///     .and_then_with(
+///         |offset: f64| {
+///             CommandBuilder::new()
+///                 .init(move || println!("Moving to {offset}"))
+///                 .is_finished(|| true)
+///                 .build()
+///         },
+///         &[],
+///     )
+///     .schedule();
+///
+/// manager.run();
+/// ```
+#[derive(Debug)]
+pub struct ValueCommand<T> {
+    command: Command,
+    value: ValueHandle<T>,
+}
+impl<T: 'static> ValueCommand<T> {
+    /// Wraps a command, `output` is called to produce the value once the command finishes.
+    pub fn new(command: Command, output: impl FnMut() -> T + 'static) -> Self {
+        let value = ValueHandle(Rc::new(RefCell::new(None)));
+        Self {
+            command: Command::Custom(Box::new(OutputCommand {
+                command,
+                output: Box::new(output),
+                value: value.clone(),
+            })),
+            value,
+        }
+    }
+
+    /// Creates a command that calls `poll` every cycle and finishes with the first value it returns.
+    pub fn poll(mut poll: impl FnMut() -> Option<T> + 'static, subsystems: Requirements) -> Self {
+        let value = ValueHandle(Rc::new(RefCell::new(None)));
+        let output = value.clone();
+        let reset = value.clone();
+        let command = CommandBuilder::new()
+            .init(move || drop(reset.take()))
+            .is_finished(move || poll().map(|polled| output.set(polled)).is_some())
+            .with_subsystems(subsystems)
+            .build();
+        Self { command, value }
+    }
+
+    /// A handle to read the value from outside of the command.
+    #[must_use]
+    pub fn handle(&self) -> ValueHandle<T> {
+        self.value.clone()
+    }
+
+    /// Runs this command, then the command built by `next` from its output.
+    ///
+    /// The next command is built when it starts,
+    /// so the subsystems it could use have to be given up front as `subsystems`.
+    pub fn and_then_with(
+        self,
+        mut next: impl FnMut(T) -> Command + 'static,
+        subsystems: Requirements,
+    ) -> Command {
+        let value = self.value;
+        self.command.before(Command::proxy(
+            move || {
+                value.take().map_or_else(
+                    || {
+                        tracing::warn!("ValueCommand finished without a value");
+                        Command::empty()
+                    },
+                    &mut next,
+                )
+            },
+            subsystems,
+        ))
+    }
+
+    /// Discards the output and returns the underlying command.
+    pub fn into_command(self) -> Command {
+        self.command
+    }
+
+    /// Schedule this command to run, see [`Command::schedule`].
+    pub fn schedule(self) {
+        self.command.schedule();
+    }
+}
+impl<T: 'static> From<ValueCommand<T>> for Command {
+    fn from(command: ValueCommand<T>) -> Self {
+        command.into_command()
+    }
+}
+
+struct OutputCommand<T> {
+    command: Command,
+    output: Box<dyn FnMut() -> T>,
+    value: ValueHandle<T>,
+}
+impl<T> CommandTrait for OutputCommand<T> {
+    fn init(&mut self) {
+        drop(self.value.take());
+        self.command.init();
+    }
+
+    fn periodic(&mut self, period: Duration) {
+        self.command.periodic(period);
+    }
+
+    fn end(&mut self, interrupted: bool) {
+        self.command.end(interrupted);
+        if !interrupted {
+            self.value.set((self.output)());
+        }
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.command.is_finished()
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.command.take_error()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.command.run_when_disabled()
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        self.command.interruption_behavior()
+    }
+
+    fn priority(&self) -> i32 {
+        self.command.priority()
+    }
+
+    fn get_requirements(&self) -> Vec<SubsystemSUID> {
+        self.command.get_requirements()
+    }
+
+    fn get_name(&self) -> String {
+        self.command.get_name()
+    }
+
+    fn wrapped_command(&self) -> Option<&Command> {
+        Some(&self.command)
+    }
+}
+
+enum FsmTrigger {
+    Condition(Box<dyn BooleanSupplier>),
+    Finished,
+}
+
+struct FsmTransition<S> {
+    from: Option<S>,
+    to: S,
+    trigger: FsmTrigger,
+}
+
+/// A command that runs a finite state machine, where each state runs a command.
+///
+/// Transitions are checked in the order they were added after the current state's command runs,
+/// at most one is taken per cycle.
+/// Leaving a state always ends its command, interrupting it if it had not finished,
+/// and the state being entered is initialized right away.
+/// States without a command do nothing until a transition leaves them.
+/// The machine finishes when it enters a [final state](FsmCommand::final_state).
+///
+/// The current state is part of the command's name so it shows up in snapshots and logs.
+///
+/// # Examples
+/// ```
+/// use frclib_commands::{Command, CommandBuilder, CommandManager, FsmCommand};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// enum Shooter {
+///     Intaking,
+///     Indexing,
+///     Shooting,
+///     Done,
+/// }
+///
+/// let mut manager = CommandManager::new();
+/// FsmCommand::new(Shooter::Intaking)
+///     .with_name(&"shooter")
+///     .state(Shooter::Intaking, Command::empty())
+///     .state(Shooter::Indexing, CommandBuilder::new().is_finished(|| true).build())
+///     .state(Shooter::Shooting, CommandBuilder::new().is_finished(|| true).build())
+///     .transition(Shooter::Intaking, Shooter::Indexing, || true)
+///     .transition_on_finish(Shooter::Indexing, Shooter::Shooting)
+///     .transition_on_finish(Shooter::Shooting, Shooter::Done)
+///     .final_state(Shooter::Done)
+///     .schedule();
+///
+/// manager.run();
+/// ```
+pub struct FsmCommand<S> {
+    name: String,
+    initial: S,
+    state: S,
+    commands: Vec<Command>,
+    state_commands: FxHashMap<S, usize>,
+    final_states: Vec<S>,
+    transitions: Vec<FsmTransition<S>>,
+    /// If the current state's command has been initialized and not ended yet.
+    state_running: bool,
+    /// If the current state's command finished on its own, states without a command never finish.
+    state_finished: bool,
+    finished: bool,
+    error: Option<CommandError>,
+}
+impl<S: Debug> Debug for FsmCommand<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FsmCommand")
+            .field("name", &self.name)
+            .field("state", &self.state)
+            .field("commands", &self.commands)
+            .field("final_states", &self.final_states)
+            .field("transitions", &self.transitions.len())
+            .finish_non_exhaustive()
+    }
+}
+impl<S: Copy + Eq + Hash + Debug + 'static> FsmCommand<S> {
+    /// Creates a state machine that starts in `initial` every time it is scheduled.
+    #[must_use]
+    pub fn new(initial: S) -> Self {
+        Self {
+            name: String::from("FSM"),
+            initial,
+            state: initial,
+            commands: Vec::new(),
+            state_commands: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            final_states: Vec::new(),
+            transitions: Vec::new(),
+            state_running: false,
+            state_finished: false,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Sets the name shown before the current state.
+    #[must_use]
+    pub fn with_name(mut self, name: &impl ToString) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets the command run while in `state`, replacing any previous one.
+    /// The state machine adopts the requirements of every state's command.
+    #[must_use]
+    pub fn state(mut self, state: S, command: Command) -> Self {
+        if let Some(&index) = self.state_commands.get(&state) {
+            self.commands[index] = command;
+        } else {
+            self.state_commands.insert(state, self.commands.len());
+            self.commands.push(command);
+        }
+        self
+    }
+
+    /// Moves from `from` to `to` when `condition` is true,
+    /// a transition to the same state restarts its command.
+    #[must_use]
+    pub fn transition(mut self, from: S, to: S, condition: impl BooleanSupplier + 'static) -> Self {
+        self.transitions.push(FsmTransition {
+            from: Some(from),
+            to,
+            trigger: FsmTrigger::Condition(Box::new(condition)),
+        });
+        self
+    }
+
+    /// Moves from any state other than `to` to `to` when `condition` is true.
+    #[must_use]
+    pub fn transition_from_any(mut self, to: S, condition: impl BooleanSupplier + 'static) -> Self {
+        self.transitions.push(FsmTransition {
+            from: None,
+            to,
+            trigger: FsmTrigger::Condition(Box::new(condition)),
+        });
+        self
+    }
+
+    /// Moves from `from` to `to` once the command of `from` finishes.
+    #[must_use]
+    pub fn transition_on_finish(mut self, from: S, to: S) -> Self {
+        self.transitions.push(FsmTransition {
+            from: Some(from),
+            to,
+            trigger: FsmTrigger::Finished,
+        });
+        self
+    }
+
+    /// Marks `state` as final, the state machine finishes as soon as it enters it.
+    #[must_use]
+    pub fn final_state(mut self, state: S) -> Self {
+        self.final_states.push(state);
+        self
+    }
+
+    /// Boxes this state machine into a [`Command`].
+    pub fn into_command(self) -> Command {
+        Command::Custom(Box::new(self))
+    }
+
+    /// Schedule this command to run, see [`Command::schedule`].
+    pub fn schedule(self) {
+        self.into_command().schedule();
+    }
+
+    fn current_command(&mut self) -> Option<&mut Command> {
+        let index = *self.state_commands.get(&self.state)?;
+        self.commands.get_mut(index)
+    }
+
+    fn enter(&mut self, state: S) {
+        tracing::debug!("{} entering state {state:?}", self.name);
+        self.state = state;
+        self.state_finished = false;
+        if self.final_states.contains(&state) {
+            self.finished = true;
+            return;
+        }
+        if let Some(command) = self.current_command() {
+            command.init();
+            let error = command.take_error();
+            self.error = error;
+            self.state_running = true;
+        }
+    }
+
+    fn exit(&mut self) {
+        if self.state_running {
+            self.state_running = false;
+            if let Some(command) = self.current_command() {
+                command.end(true);
+            }
+        }
+    }
+}
+impl<S: Copy + Eq + Hash + Debug + 'static> CommandTrait for FsmCommand<S> {
+    fn init(&mut self) {
+        self.finished = false;
+        self.error = None;
+        self.enter(self.initial);
+    }
+
+    fn periodic(&mut self, period: Duration) {
+        if self.finished {
+            return;
+        }
+        if self.state_running {
+            let Some(command) = self.current_command() else {
+                return;
+            };
+            command.periodic(period);
+            if let Some(error) = command.take_error() {
+                self.error = Some(error);
+                return;
+            }
+            if command.is_finished() {
+                command.end(false);
+                self.state_running = false;
+                self.state_finished = true;
+            }
+        }
+
+        let state = self.state;
+        let state_finished = self.state_finished;
+        let next = self
+            .transitions
+            .iter()
+            .find(|transition| {
+                let applies = match transition.from {
+                    Some(from) => from == state,
+                    None => transition.to != state,
+                };
+                applies
+                    && match &transition.trigger {
+                        FsmTrigger::Condition(condition) => condition.get_as_boolean(),
+                        FsmTrigger::Finished => state_finished,
+                    }
+            })
+            .map(|transition| transition.to);
+        if let Some(next) = next {
+            self.exit();
+            self.enter(next);
+        }
+    }
+
+    fn end(&mut self, _interrupted: bool) {
+        self.exit();
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.finished
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.commands.iter().all(CommandTrait::run_when_disabled)
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        group_interruption_behavior(&self.commands)
+    }
+
+    fn priority(&self) -> i32 {
+        group_priority(&self.commands)
+    }
+
+    fn get_requirements(&self) -> Vec<SubsystemSUID> {
+        group_requirements(&self.commands)
+    }
+
+    fn get_name(&self) -> String {
+        format!("{}[{:?}]", self.name, self.state)
+    }
+}
+impl<S: Copy + Eq + Hash + Debug + 'static> From<FsmCommand<S>> for Command {
+    fn from(command: FsmCommand<S>) -> Self {
+        command.into_command()
+    }
+}
+
 #[allow(missing_copy_implementations)]
 #[derive(Debug)]
 pub struct WaitCommand {
@@ -577,6 +1201,62 @@ impl CommandTrait for WaitCommand {
     }
 }
 
+/// The requirements of a parallel group, no two members may require the same subsystem.
+fn parallel_requirements(commands: &[Command]) -> Result<HashSet<SubsystemSUID>, CompositionError> {
+    let mut requirements = HashSet::new();
+    for command in commands {
+        let member = command.get_requirements();
+        if let Some(overlap) = member.iter().find(|suid| requirements.contains(*suid)) {
+            return Err(CompositionError::OverlappingRequirements(*overlap));
+        }
+        requirements.extend(member);
+    }
+    Ok(requirements)
+}
+
+fn expect_composed(command: Result<Command, CompositionError>) -> Command {
+    command.unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// A group cancels incoming commands if any of its members would.
+pub(crate) fn group_interruption_behavior<'a>(
+    commands: impl IntoIterator<Item = &'a Command>,
+) -> InterruptionBehavior {
+    if commands
+        .into_iter()
+        .any(|command| command.interruption_behavior() == InterruptionBehavior::CancelIncoming)
+    {
+        InterruptionBehavior::CancelIncoming
+    } else {
+        InterruptionBehavior::CancelSelf
+    }
+}
+
+/// A group has the highest priority of its members.
+pub(crate) fn group_priority<'a>(commands: impl IntoIterator<Item = &'a Command>) -> i32 {
+    commands
+        .into_iter()
+        .map(CommandTrait::priority)
+        .max()
+        .unwrap_or_default()
+}
+
+/// The requirements of every member of a group, in order without duplicates.
+pub(crate) fn group_requirements<'a>(
+    commands: impl IntoIterator<Item = &'a Command>,
+) -> Vec<SubsystemSUID> {
+    let mut requirements = Vec::new();
+    for requirement in commands
+        .into_iter()
+        .flat_map(CommandTrait::get_requirements)
+    {
+        if !requirements.contains(&requirement) {
+            requirements.push(requirement);
+        }
+    }
+    requirements
+}
+
 #[derive(Debug)]
 pub struct NamedCommand {
     name: String,
@@ -599,6 +1279,22 @@ impl CommandTrait for NamedCommand {
         self.command.is_finished()
     }
 
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.command.take_error()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.command.run_when_disabled()
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        self.command.interruption_behavior()
+    }
+
+    fn priority(&self) -> i32 {
+        self.command.priority()
+    }
+
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.command.get_requirements()
     }
@@ -630,6 +1326,22 @@ impl CommandTrait for ExtraRequirementsCommand {
         self.command.is_finished()
     }
 
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.command.take_error()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.command.run_when_disabled()
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        self.command.interruption_behavior()
+    }
+
+    fn priority(&self) -> i32 {
+        self.command.priority()
+    }
+
     fn get_requirements(&self) -> Vec<SubsystemSUID> {
         self.command
             .get_requirements()
@@ -643,6 +1355,58 @@ impl CommandTrait for ExtraRequirementsCommand {
     }
 }
 
+/// Overrides the priority, interruption behavior and disabled behavior of a command.
+#[derive(Debug)]
+pub struct InterruptibleCommand {
+    command: Box<Command>,
+    priority: Option<i32>,
+    interruption_behavior: Option<InterruptionBehavior>,
+    run_when_disabled: Option<bool>,
+}
+impl CommandTrait for InterruptibleCommand {
+    fn init(&mut self) {
+        self.command.init();
+    }
+
+    fn periodic(&mut self, period: Duration) {
+        self.command.periodic(period);
+    }
+
+    fn end(&mut self, interrupted: bool) {
+        self.command.end(interrupted);
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.command.is_finished()
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.command.take_error()
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        self.run_when_disabled
+            .unwrap_or_else(|| self.command.run_when_disabled())
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        self.interruption_behavior
+            .unwrap_or_else(|| self.command.interruption_behavior())
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority.unwrap_or_else(|| self.command.priority())
+    }
+
+    fn get_requirements(&self) -> Vec<SubsystemSUID> {
+        self.command.get_requirements()
+    }
+
+    fn get_name(&self) -> String {
+        self.command.get_name()
+    }
+}
+
 #[must_use]
 pub enum Command {
     Parallel(ParallelCommand),
@@ -654,6 +1418,7 @@ pub enum Command {
     Wait(WaitCommand),
     Proxy(ProxyCommand),
     ExtraRequirments(ExtraRequirementsCommand),
+    Interruptible(InterruptibleCommand),
 }
 impl Debug for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -676,6 +1441,10 @@ impl Debug for Command {
                 .debug_struct("ExtraRequirments")
                 .field("command", command)
                 .finish(),
+            Self::Interruptible(command) => f
+                .debug_struct("Interruptible")
+                .field("command", command)
+                .finish(),
         }
     }
 }
@@ -691,6 +1460,7 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.init(),
             Self::Proxy(command) => command.init(),
             Self::ExtraRequirments(command) => command.init(),
+            Self::Interruptible(command) => command.init(),
         }
     }
 
@@ -705,6 +1475,7 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.periodic(period),
             Self::Proxy(command) => command.periodic(period),
             Self::ExtraRequirments(command) => command.periodic(period),
+            Self::Interruptible(command) => command.periodic(period),
         }
     }
 
@@ -719,6 +1490,7 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.end(interrupted),
             Self::Proxy(command) => command.end(interrupted),
             Self::ExtraRequirments(command) => command.end(interrupted),
+            Self::Interruptible(command) => command.end(interrupted),
         }
     }
 
@@ -733,6 +1505,67 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.is_finished(),
             Self::Proxy(command) => command.is_finished(),
             Self::ExtraRequirments(command) => command.is_finished(),
+            Self::Interruptible(command) => command.is_finished(),
+        }
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        match self {
+            Self::Parallel(command) => command.take_error(),
+            Self::Sequential(command) => command.take_error(),
+            Self::Simple(command) => command.take_error(),
+            Self::Const(command) => command.take_error(),
+            Self::Custom(command) => command.take_error(),
+            Self::Named(command) => command.take_error(),
+            Self::Wait(command) => command.take_error(),
+            Self::Proxy(command) => command.take_error(),
+            Self::ExtraRequirments(command) => command.take_error(),
+            Self::Interruptible(command) => command.take_error(),
+        }
+    }
+
+    fn run_when_disabled(&self) -> bool {
+        match self {
+            Self::Parallel(command) => command.run_when_disabled(),
+            Self::Sequential(command) => command.run_when_disabled(),
+            Self::Simple(command) => command.run_when_disabled(),
+            Self::Const(command) => command.run_when_disabled(),
+            Self::Custom(command) => command.run_when_disabled(),
+            Self::Named(command) => command.run_when_disabled(),
+            Self::Wait(command) => command.run_when_disabled(),
+            Self::Proxy(command) => command.run_when_disabled(),
+            Self::ExtraRequirments(command) => command.run_when_disabled(),
+            Self::Interruptible(command) => command.run_when_disabled(),
+        }
+    }
+
+    fn interruption_behavior(&self) -> InterruptionBehavior {
+        match self {
+            Self::Parallel(command) => command.interruption_behavior(),
+            Self::Sequential(command) => command.interruption_behavior(),
+            Self::Simple(command) => command.interruption_behavior(),
+            Self::Const(command) => command.interruption_behavior(),
+            Self::Custom(command) => command.interruption_behavior(),
+            Self::Named(command) => command.interruption_behavior(),
+            Self::Wait(command) => command.interruption_behavior(),
+            Self::Proxy(command) => command.interruption_behavior(),
+            Self::ExtraRequirments(command) => command.interruption_behavior(),
+            Self::Interruptible(command) => command.interruption_behavior(),
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        match self {
+            Self::Parallel(command) => command.priority(),
+            Self::Sequential(command) => command.priority(),
+            Self::Simple(command) => command.priority(),
+            Self::Const(command) => command.priority(),
+            Self::Custom(command) => command.priority(),
+            Self::Named(command) => command.priority(),
+            Self::Wait(command) => command.priority(),
+            Self::Proxy(command) => command.priority(),
+            Self::ExtraRequirments(command) => command.priority(),
+            Self::Interruptible(command) => command.priority(),
         }
     }
 
@@ -747,6 +1580,7 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.get_requirements(),
             Self::Proxy(command) => command.get_requirements(),
             Self::ExtraRequirments(command) => command.get_requirements(),
+            Self::Interruptible(command) => command.get_requirements(),
         }
     }
 
@@ -761,67 +1595,128 @@ impl CommandTrait for Command {
             Self::Wait(command) => command.get_name(),
             Self::Proxy(command) => command.get_name(),
             Self::ExtraRequirments(command) => command.get_name(),
+            Self::Interruptible(command) => command.get_name(),
+        }
+    }
+}
+
+impl Command {
+    /// Returns the structure of this command as a tree,
+    /// composed commands have their members as children.
+    #[must_use]
+    pub fn tree(&self) -> CommandNode {
+        let (kind, children) = match self {
+            Self::Parallel(command) => (
+                CommandKind::Parallel(command.kind),
+                command.commands.iter().map(Self::tree).collect(),
+            ),
+            Self::Sequential(command) => (
+                CommandKind::Sequential,
+                command.commands.iter().map(Self::tree).collect(),
+            ),
+            Self::Simple(_) => (CommandKind::Simple, Vec::new()),
+            Self::Const(_) => (CommandKind::Const, Vec::new()),
+            Self::Custom(command) => match command.wrapped_command() {
+                Some(wrapped) => return wrapped.tree(),
+                None => (CommandKind::Custom, Vec::new()),
+            },
+            Self::Named(command) => (CommandKind::Named, vec![command.command.tree()]),
+            Self::Wait(_) => (CommandKind::Wait, Vec::new()),
+            Self::Proxy(command) => (
+                CommandKind::Proxy,
+                command
+                    .command
+                    .as_deref()
+                    .map(Self::tree)
+                    .into_iter()
+                    .collect(),
+            ),
+            Self::ExtraRequirments(command) => {
+                (CommandKind::ExtraRequirements, vec![command.command.tree()])
+            }
+            // only changes how the command is scheduled, not its structure
+            Self::Interruptible(command) => return command.command.tree(),
+        };
+        CommandNode {
+            name: self.get_name(),
+            kind,
+            requirements: self.get_requirements(),
+            children,
         }
     }
 }
 
 impl Command {
     /// Constructs a Parallel Command of self and other
+    ///
+    /// # Panics
+    /// If self and other require the same subsystem,
+    /// if you want to handle this error use [`Command::try_along_with`]
     pub fn along_with(self, other: Self) -> Self {
-        Self::Parallel(ParallelCommand {
-            requirements: self
-                .get_requirements()
-                .into_iter()
-                .chain(other.get_requirements())
-                .collect(),
-            commands: vec![self, other],
-            finished: vec![false, false],
-            race: false,
-        })
+        expect_composed(self.try_along_with(other))
+    }
+
+    /// Constructs a Parallel Command of self and other
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if self and other require the same subsystem
+    pub fn try_along_with(self, other: Self) -> Result<Self, CompositionError> {
+        Self::try_parallel(vec![self, other])
     }
 
     /// Constructs a Parallel Command of self and others
+    ///
+    /// # Panics
+    /// If any of the commands require the same subsystem,
+    /// if you want to handle this error use [`Command::try_along_with_many`]
     pub fn along_with_many(self, others: Vec<Self>) -> Self {
+        expect_composed(self.try_along_with_many(others))
+    }
+
+    /// Constructs a Parallel Command of self and others
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if any of the commands require the same subsystem
+    pub fn try_along_with_many(self, others: Vec<Self>) -> Result<Self, CompositionError> {
         let mut commands = vec![self];
         commands.extend(others);
-        Self::Parallel(ParallelCommand {
-            finished: vec![false; commands.len()],
-            requirements: commands
-                .iter()
-                .flat_map(CommandTrait::get_requirements)
-                .collect(),
-            commands,
-            race: false,
-        })
+        Self::try_parallel(commands)
     }
 
     /// Constructs a Parallel Command of self and other that will finish when one of them finishes
+    ///
+    /// # Panics
+    /// If self and other require the same subsystem,
+    /// if you want to handle this error use [`Command::try_race_with`]
     pub fn race_with(self, other: Self) -> Self {
-        Self::Parallel(ParallelCommand {
-            requirements: self
-                .get_requirements()
-                .into_iter()
-                .chain(other.get_requirements())
-                .collect(),
-            commands: vec![self, other],
-            finished: vec![false, false],
-            race: true,
-        })
+        expect_composed(self.try_race_with(other))
+    }
+
+    /// Constructs a Parallel Command of self and other that will finish when one of them finishes
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if self and other require the same subsystem
+    pub fn try_race_with(self, other: Self) -> Result<Self, CompositionError> {
+        Self::try_race(vec![self, other])
     }
 
     /// Constructs a Parallel Command of self and others that will finish when one of them finishes
+    ///
+    /// # Panics
+    /// If any of the commands require the same subsystem,
+    /// if you want to handle this error use [`Command::try_race_with_many`]
     pub fn race_with_many(self, others: Vec<Self>) -> Self {
+        expect_composed(self.try_race_with_many(others))
+    }
+
+    /// Constructs a Parallel Command of self and others that will finish when one of them finishes
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if any of the commands require the same subsystem
+    pub fn try_race_with_many(self, others: Vec<Self>) -> Result<Self, CompositionError> {
         let mut commands = vec![self];
         commands.extend(others);
-        Self::Parallel(ParallelCommand {
-            finished: vec![false; commands.len()],
-            requirements: commands
-                .iter()
-                .flat_map(CommandTrait::get_requirements)
-                .collect(),
-            commands,
-            race: true,
-        })
+        Self::try_race(commands)
     }
 
     pub fn timeout(self, duration: Duration) -> Self {
@@ -839,6 +1734,7 @@ impl Command {
                 .collect(),
             commands: vec![self, other],
             current: 0,
+            error: None,
         })
     }
 
@@ -853,6 +1749,7 @@ impl Command {
                 .collect(),
             commands: vec![other, self],
             current: 0,
+            error: None,
         })
     }
 
@@ -868,6 +1765,7 @@ impl Command {
                 .collect(),
             commands,
             current: 0,
+            error: None,
         })
     }
 
@@ -887,6 +1785,41 @@ impl Command {
         })
     }
 
+    fn interruptible(self) -> InterruptibleCommand {
+        match self {
+            Self::Interruptible(command) => command,
+            command => InterruptibleCommand {
+                command: Box::new(command),
+                priority: None,
+                interruption_behavior: None,
+                run_when_disabled: None,
+            },
+        }
+    }
+
+    /// Sets the priority of this command, see [`CommandTrait::priority`]
+    pub fn with_priority(self, priority: i32) -> Self {
+        let mut command = self.interruptible();
+        command.priority = Some(priority);
+        Self::Interruptible(command)
+    }
+
+    /// Sets how this command reacts to incoming commands of equal priority,
+    /// see [`CommandTrait::interruption_behavior`]
+    pub fn with_interruption_behavior(self, interruption_behavior: InterruptionBehavior) -> Self {
+        let mut command = self.interruptible();
+        command.interruption_behavior = Some(interruption_behavior);
+        Self::Interruptible(command)
+    }
+
+    /// Sets if this command can be scheduled and keep running while the robot is disabled,
+    /// see [`CommandTrait::run_when_disabled`]
+    pub fn ignoring_disable(self, run_when_disabled: bool) -> Self {
+        let mut command = self.interruptible();
+        command.run_when_disabled = Some(run_when_disabled);
+        Self::Interruptible(command)
+    }
+
     /// Constructs a Wait Command that will wait for the given seconds
     pub fn wait_for(duration: Duration) -> Self {
         Self::Wait(WaitCommand {
@@ -900,6 +1833,17 @@ impl Command {
         Self::Custom(command)
     }
 
+    /// Creates a command that builds the command to run every time it is initialized.
+    ///
+    /// As the command is not known ahead of time its requirements have to be given.
+    pub fn proxy(supplier: impl FnMut() -> Command + 'static, subsystems: Requirements) -> Self {
+        Self::Proxy(ProxyCommand {
+            command_supplier: Box::new(supplier),
+            command: None,
+            requirements: subsystems.iter().map(|s| s.suid()).collect(),
+        })
+    }
+
     /// Creates an empty command with no requirements
     pub fn empty() -> Self {
         CommandBuilder::init_only(|| {}, &[])
@@ -913,28 +1857,78 @@ impl Command {
     /// The commands do not actually run in parallel,
     /// they run sequentially in the order they are given but they are all run every cycle
     /// unlike a sequential command where only one command is run every cycle.
+    ///
+    /// # Panics
+    /// If any of the commands require the same subsystem,
+    /// if you want to handle this error use [`Command::try_parallel`]
     pub fn parallel(commands: Vec<Command>) -> Command {
-        Command::Parallel(ParallelCommand {
+        expect_composed(Self::try_parallel(commands))
+    }
+
+    /// Same as [`Command::parallel`]
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if any of the commands require the same subsystem
+    pub fn try_parallel(commands: Vec<Command>) -> Result<Command, CompositionError> {
+        Ok(Command::Parallel(ParallelCommand {
             finished: vec![false; commands.len()],
-            requirements: commands
-                .iter()
-                .flat_map(CommandTrait::get_requirements)
-                .collect(),
+            requirements: parallel_requirements(&commands)?,
             commands,
-            race: false,
-        })
+            kind: ParallelKind::All,
+            error: None,
+        }))
     }
 
+    /// Same as [`Command::parallel`] but finishes when any of the given commands finish.
+    ///
+    /// # Panics
+    /// If any of the commands require the same subsystem,
+    /// if you want to handle this error use [`Command::try_race`]
     pub fn race(commands: Vec<Command>) -> Command {
-        Command::Parallel(ParallelCommand {
+        expect_composed(Self::try_race(commands))
+    }
+
+    /// Same as [`Command::race`]
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if any of the commands require the same subsystem
+    pub fn try_race(commands: Vec<Command>) -> Result<Command, CompositionError> {
+        Ok(Command::Parallel(ParallelCommand {
             finished: vec![false; commands.len()],
-            requirements: commands
-                .iter()
-                .flat_map(CommandTrait::get_requirements)
-                .collect(),
+            requirements: parallel_requirements(&commands)?,
             commands,
-            race: true,
-        })
+            kind: ParallelKind::Race,
+            error: None,
+        }))
+    }
+
+    /// Same as [`Command::parallel`] but finishes when `deadline` finishes,
+    /// interrupting any of the `others` that are still running.
+    ///
+    /// # Panics
+    /// If any of the commands require the same subsystem,
+    /// if you want to handle this error use [`Command::try_deadline`]
+    pub fn deadline(deadline: Command, others: Vec<Command>) -> Command {
+        expect_composed(Self::try_deadline(deadline, others))
+    }
+
+    /// Same as [`Command::deadline`]
+    ///
+    /// # Errors
+    /// - [`CompositionError::OverlappingRequirements`] if any of the commands require the same subsystem
+    pub fn try_deadline(
+        deadline: Command,
+        others: Vec<Command>,
+    ) -> Result<Command, CompositionError> {
+        let mut commands = vec![deadline];
+        commands.extend(others);
+        Ok(Command::Parallel(ParallelCommand {
+            finished: vec![false; commands.len()],
+            requirements: parallel_requirements(&commands)?,
+            commands,
+            kind: ParallelKind::Deadline,
+            error: None,
+        }))
     }
 
     pub fn sequential(commands: Vec<Command>) -> Command {
@@ -945,6 +1939,7 @@ impl Command {
                 .collect(),
             commands,
             current: 0,
+            error: None,
         })
     }
 
@@ -1023,6 +2018,7 @@ impl From<Command> for Box<dyn CommandTrait> {
             Command::Wait(command) => Box::new(command),
             Command::Proxy(command) => Box::new(command),
             Command::ExtraRequirments(command) => Box::new(command),
+            Command::Interruptible(command) => Box::new(command),
         }
     }
 }
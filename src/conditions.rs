@@ -1,4 +1,15 @@
-use std::{cell::Cell, fmt::Debug, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt::Debug,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::Receiver,
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use super::{Command, CommandIndex};
 
@@ -11,21 +22,60 @@ impl<F: Fn() -> bool> BooleanSupplier for F {
     }
 }
 
+static NEXT_EVENT_LOOP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A handle to a group of conditional bindings that are only polled on demand.
+///
+/// Bindings made through a [`Condition`] with an event loop attached
+/// (see [`Condition::with_event_loop`]) are not polled by the command manager every cycle,
+/// instead they are polled when [`CommandManager::poll_event_loop`](crate::CommandManager::poll_event_loop)
+/// is called or while the loop is attached with
+/// [`CommandManager::attach_event_loop`](crate::CommandManager::attach_event_loop).
+///
+/// This allows for keeping test-mode, pit-mode and match bindings separate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventLoop(u64);
+impl EventLoop {
+    /// Creates a new event loop handle with no bindings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(NEXT_EVENT_LOOP_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ConditionalScheduler {
     condition: Condition,
     command_slot: Option<Command>,
     idx_slot: Option<CommandIndex>,
+    event_loop: Option<EventLoop>,
 }
 impl ConditionalScheduler {
     #[must_use]
-    pub const fn new(condition: Condition, command: Command) -> Self {
+    pub const fn new(
+        condition: Condition,
+        command: Command,
+        event_loop: Option<EventLoop>,
+    ) -> Self {
         Self {
             condition,
             command_slot: Some(command),
             idx_slot: None,
+            event_loop,
         }
     }
+    pub const fn event_loop(&self) -> Option<EventLoop> {
+        self.event_loop
+    }
+    pub const fn index(&self) -> Option<CommandIndex> {
+        self.idx_slot
+    }
     pub fn exchange(&mut self, idx: CommandIndex) -> Command {
         self.idx_slot = Some(idx);
         self.command_slot
@@ -45,11 +95,13 @@ impl ConditionalScheduler {
 #[allow(missing_debug_implementations)]
 pub struct Condition {
     cond: Rc<dyn BooleanSupplier>,
+    event_loop: Option<EventLoop>,
 }
 impl Debug for Condition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Condition")
             .field("cond", &"dyn BooleanSupplier")
+            .field("event_loop", &self.event_loop)
             .finish()
     }
 }
@@ -62,13 +114,39 @@ impl Condition {
     pub fn new<F: Fn() -> bool + 'static>(cond: F) -> Self {
         Self {
             cond: Rc::new(cond),
+            event_loop: None,
         }
     }
+
+    /// Creates a condition that reads a flag shared with another thread,
+    /// such as a vision or CAN reader thread.
+    #[must_use]
+    pub fn from_atomic_bool(flag: Arc<AtomicBool>) -> Self {
+        Self::new(move || flag.load(Ordering::Acquire))
+    }
+
+    /// Creates a condition that latches the latest value sent over the channel.
+    ///
+    /// Every poll drains all pending values and keeps the last one,
+    /// the condition is false until the first value is received
+    /// and keeps its last value if the sender disconnects.
+    #[must_use]
+    pub fn from_receiver(receiver: Receiver<bool>) -> Self {
+        let latest = Cell::new(false);
+        Self::new(move || {
+            if let Some(value) = receiver.try_iter().last() {
+                latest.set(value);
+            }
+            latest.get()
+        })
+    }
+
     #[must_use]
     pub fn and<F: Fn() -> bool + 'static>(&self, cond: F) -> Self {
         let slf_cond = self.cond.clone();
         Self {
             cond: Rc::new(move || slf_cond.get_as_boolean() && cond()),
+            event_loop: self.event_loop,
         }
     }
     #[must_use]
@@ -76,6 +154,7 @@ impl Condition {
         let slf_cond = self.cond.clone();
         Self {
             cond: Rc::new(move || slf_cond.get_as_boolean() | cond()),
+            event_loop: self.event_loop,
         }
     }
     #[must_use]
@@ -83,6 +162,134 @@ impl Condition {
         let slf_cond = self.cond.clone();
         Self {
             cond: Rc::new(move || !slf_cond.get_as_boolean()),
+            event_loop: self.event_loop,
+        }
+    }
+
+    /// Returns a copy of this condition whose bindings will be added to the given [`EventLoop`]
+    /// instead of being polled every cycle by the command manager.
+    #[must_use]
+    pub fn with_event_loop(&self, event_loop: EventLoop) -> Self {
+        Self {
+            cond: self.cond.clone(),
+            event_loop: Some(event_loop),
+        }
+    }
+
+    /// Returns the event loop bindings of this condition are added to, if any.
+    #[must_use]
+    pub const fn event_loop(&self) -> Option<EventLoop> {
+        self.event_loop
+    }
+
+    /// Returns a condition that is only true on the poll where this condition goes from false to true.
+    ///
+    /// Edges are tracked per returned condition, so each call starts with its own history.
+    #[must_use]
+    pub fn rising_edge(&self) -> Self {
+        let last_poll = Cell::new(false);
+        let slf_cond = self.cond.clone();
+        Self {
+            cond: Rc::new(move || {
+                let poll = slf_cond.get_as_boolean();
+                let last_poll_val = last_poll.replace(poll);
+                !last_poll_val && poll
+            }),
+            event_loop: self.event_loop,
+        }
+    }
+
+    /// Returns a condition that is only true on the poll where this condition goes from true to false.
+    ///
+    /// Edges are tracked per returned condition, so each call starts with its own history.
+    #[must_use]
+    pub fn falling_edge(&self) -> Self {
+        let last_poll = Cell::new(false);
+        let slf_cond = self.cond.clone();
+        Self {
+            cond: Rc::new(move || {
+                let poll = slf_cond.get_as_boolean();
+                let last_poll_val = last_poll.replace(poll);
+                last_poll_val && !poll
+            }),
+            event_loop: self.event_loop,
+        }
+    }
+
+    /// Returns a condition that is true for a single poll once this condition
+    /// has had `count` rising edges within `window`, for example a double tap.
+    ///
+    /// Presses are timed with the command manager clock, see [`now`](crate::manager::now).
+    /// A `count` of 0 is treated as 1, firing on every rising edge.
+    #[must_use]
+    pub fn multi_press(&self, count: usize, window: Duration) -> Self {
+        let count = count.max(1);
+        let edge = self.rising_edge();
+        let presses = RefCell::new(VecDeque::<Instant>::with_capacity(count));
+        Self {
+            cond: Rc::new(move || {
+                if !edge.get_as_boolean() {
+                    return false;
+                }
+                let now = crate::manager::now();
+                let mut presses = presses.borrow_mut();
+                presses.push_back(now);
+                while presses
+                    .front()
+                    .is_some_and(|first| now.duration_since(*first) > window)
+                {
+                    presses.pop_front();
+                }
+                if presses.len() >= count {
+                    presses.clear();
+                    true
+                } else {
+                    false
+                }
+            }),
+            event_loop: self.event_loop,
+        }
+    }
+
+    /// Returns a condition that is true while every given condition is true
+    /// and all of them became true within `window` of each other,
+    /// for example "both bumpers within 200ms".
+    ///
+    /// Presses are timed with the command manager clock, see [`now`](crate::manager::now).
+    /// The returned condition uses the event loop of the first condition, if any.
+    #[must_use]
+    pub fn chord(conditions: &[Self], window: Duration) -> Self {
+        let event_loop = conditions.first().and_then(Self::event_loop);
+        let conditions = conditions.to_vec();
+        // each input is polled once per cycle so stateful conditions see every poll,
+        // edges are found against the previous poll
+        let held = RefCell::new(vec![false; conditions.len()]);
+        let pressed_at = RefCell::new(vec![None::<Instant>; conditions.len()]);
+        Self {
+            cond: Rc::new(move || {
+                let now = crate::manager::now();
+                let mut held = held.borrow_mut();
+                let mut pressed_at = pressed_at.borrow_mut();
+                let mut all_held = !conditions.is_empty();
+                for (i, cond) in conditions.iter().enumerate() {
+                    let poll = cond.get_as_boolean();
+                    if poll && !held[i] {
+                        pressed_at[i] = Some(now);
+                    }
+                    held[i] = poll;
+                    all_held &= poll;
+                }
+                if !all_held {
+                    return false;
+                }
+                let first = pressed_at.iter().flatten().min();
+                let last = pressed_at.iter().flatten().max();
+                match (first, last) {
+                    (Some(first), Some(last)) => last.duration_since(*first) <= window,
+                    _ => false,
+                }
+            }),
+            event_loop,
         }
     }
 
@@ -94,14 +301,8 @@ impl Condition {
     /// due to being on a different thread.
     #[allow(clippy::return_self_not_must_use, clippy::must_use_candidate)]
     pub fn on_true(&self, command: Command) -> Self {
-        let last_poll = Cell::new(false);
-        let slf_cond = self.cond.clone();
-        let condition = Self::new(move || {
-            let poll = slf_cond.get_as_boolean();
-            let last_poll_val = last_poll.replace(poll);
-            !last_poll_val && poll
-        });
-        let cond_sched = ConditionalScheduler::new(condition, command);
+        let condition = self.rising_edge();
+        let cond_sched = ConditionalScheduler::new(condition, command, self.event_loop);
         super::manager::add_cond_scheduler(cond_sched)
             .expect("Failed to add conditional scheduler");
 
@@ -116,14 +317,8 @@ impl Condition {
     /// due to being on a different thread.
     #[allow(clippy::return_self_not_must_use, clippy::must_use_candidate)]
     pub fn on_false(&self, command: Command) -> Self {
-        let last_poll = Cell::new(false);
-        let slf_cond = self.cond.clone();
-        let condition = Self::new(move || {
-            let poll = slf_cond.get_as_boolean();
-            let last_poll_val = last_poll.replace(poll);
-            last_poll_val && !poll
-        });
-        let cond_sched = ConditionalScheduler::new(condition, command);
+        let condition = self.falling_edge();
+        let cond_sched = ConditionalScheduler::new(condition, command, self.event_loop);
         super::manager::add_cond_scheduler(cond_sched)
             .expect("Failed to add conditional scheduler");
 
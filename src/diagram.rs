@@ -0,0 +1,173 @@
+//! Graphviz DOT and Mermaid export of composed command trees.
+//!
+//! # Examples
+//! ```
+//! use frclib_commands::Command;
+//! use std::time::Duration;
+//!
+//! let auto = Command::empty()
+//!     .with_name(&"drive")
+//!     .before(Command::wait_for(Duration::from_secs(1)));
+//!
+//! let dot = auto.tree().to_dot();
+//! assert!(dot.starts_with("digraph"));
+//! let mermaid = auto.tree().to_mermaid();
+//! assert!(mermaid.starts_with("flowchart TD"));
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    snapshot::{CommandKind, CommandNode, ParallelKind},
+    SubsystemSUID,
+};
+
+/// Names a subsystem in a diagram, see [`CommandManager::subsystem_name`](crate::CommandManager::subsystem_name).
+pub type SubsystemNamer<'a> = &'a dyn Fn(SubsystemSUID) -> String;
+
+fn default_namer(suid: SubsystemSUID) -> String {
+    format!("{suid:#x}")
+}
+
+impl CommandNode {
+    /// Renders this tree as a Graphviz DOT digraph,
+    /// requirements are shown as subsystem ids.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&default_namer)
+    }
+
+    /// Renders this tree as a Graphviz DOT digraph,
+    /// using `subsystem_name` to label requirements.
+    #[must_use]
+    pub fn to_dot_with(&self, subsystem_name: SubsystemNamer) -> String {
+        let mut out = String::from("digraph command {\n    node [fontname=\"sans-serif\"];\n");
+        let mut next_id = 0;
+        write_dot(self, subsystem_name, &mut next_id, &mut out);
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders this tree as a Mermaid flowchart,
+    /// requirements are shown as subsystem ids.
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        self.to_mermaid_with(&default_namer)
+    }
+
+    /// Renders this tree as a Mermaid flowchart,
+    /// using `subsystem_name` to label requirements.
+    #[must_use]
+    pub fn to_mermaid_with(&self, subsystem_name: SubsystemNamer) -> String {
+        let mut out = String::from("flowchart TD\n");
+        let mut next_id = 0;
+        write_mermaid(self, subsystem_name, &mut next_id, &mut out);
+        out
+    }
+
+    const fn is_group(&self) -> bool {
+        matches!(
+            self.kind,
+            CommandKind::Parallel(_) | CommandKind::Sequential
+        )
+    }
+
+    /// The lines of text shown inside the node, unescaped.
+    fn label_lines(&self, subsystem_name: SubsystemNamer) -> Vec<String> {
+        let title = match self.kind {
+            CommandKind::Parallel(ParallelKind::All) => String::from("parallel"),
+            CommandKind::Parallel(ParallelKind::Race) => String::from("race"),
+            CommandKind::Parallel(ParallelKind::Deadline) => String::from("deadline"),
+            CommandKind::Sequential => String::from("sequence"),
+            CommandKind::Proxy => String::from("proxy"),
+            CommandKind::ExtraRequirements => String::from("extra requirements"),
+            CommandKind::Simple
+            | CommandKind::Const
+            | CommandKind::Custom
+            | CommandKind::Named
+            | CommandKind::Wait => self.name.clone(),
+        };
+        let mut lines = vec![title];
+        if !self.requirements.is_empty() {
+            let mut names = self
+                .requirements
+                .iter()
+                .map(|suid| subsystem_name(*suid))
+                .collect::<Vec<_>>();
+            names.sort();
+            lines.push(format!("requires: {}", names.join(", ")));
+        }
+        lines
+    }
+
+    /// Edge labels from this node to each child, sequences number their steps.
+    fn edge_label(&self, child: usize) -> Option<String> {
+        match self.kind {
+            CommandKind::Sequential => Some((child + 1).to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn write_dot(
+    node: &CommandNode,
+    namer: SubsystemNamer,
+    next_id: &mut usize,
+    out: &mut String,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let label = node
+        .label_lines(namer)
+        .iter()
+        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<_>>()
+        .join("\\n");
+    let shape = if node.is_group() { "box" } else { "ellipse" };
+    let _ = writeln!(out, "    n{id} [label=\"{label}\", shape={shape}];");
+    for (i, child) in node.children.iter().enumerate() {
+        let child_id = write_dot(child, namer, next_id, out);
+        match node.edge_label(i) {
+            Some(edge) => {
+                let _ = writeln!(out, "    n{id} -> n{child_id} [label=\"{edge}\"];");
+            }
+            None => {
+                let _ = writeln!(out, "    n{id} -> n{child_id};");
+            }
+        }
+    }
+    id
+}
+
+fn write_mermaid(
+    node: &CommandNode,
+    namer: SubsystemNamer,
+    next_id: &mut usize,
+    out: &mut String,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let label = node
+        .label_lines(namer)
+        .iter()
+        .map(|line| line.replace('"', "#quot;"))
+        .collect::<Vec<_>>()
+        .join("<br/>");
+    if node.is_group() {
+        let _ = writeln!(out, "    n{id}[\"{label}\"]");
+    } else {
+        let _ = writeln!(out, "    n{id}([\"{label}\"])");
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        let child_id = write_mermaid(child, namer, next_id, out);
+        match node.edge_label(i) {
+            Some(edge) => {
+                let _ = writeln!(out, "    n{id} -->|{edge}| n{child_id}");
+            }
+            None => {
+                let _ = writeln!(out, "    n{id} --> n{child_id}");
+            }
+        }
+    }
+    id
+}
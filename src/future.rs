@@ -0,0 +1,277 @@
+//! Commands written as `async` blocks, polled once per cycle by the command manager.
+//!
+//! No runtime is needed, futures are polled with a no-op waker every time the command runs
+//! and dropped when the command is interrupted.
+//! Commands can be awaited inside them to run them to completion,
+//! resolving to the error of the awaited command if it failed.
+//!
+//! # Examples
+//! ```
+//! use frclib_commands::{future, Command, CommandBuilder, CommandManager};
+//! use std::time::Duration;
+//!
+//! fn drive(speed: f64) -> Command {
+//!     CommandBuilder::new()
+//!         .init(move || println!("Driving at {speed}"))
+//!         .is_finished(|| true)
+//!         .build()
+//! }
+//!
+//! let mut manager = CommandManager::new();
+//! Command::from_async(
+//!     async move {
+//!         drive(1.0).await?;
+//!         future::wait(Duration::from_millis(20)).await;
+//!         drive(0.0).await
+//!     },
+//!     &[],
+//! )
+//! .schedule();
+//!
+//! for _ in 0..5 {
+//!     manager.run();
+//!     std::thread::sleep(Duration::from_millis(10));
+//! }
+//! ```
+
+use std::{
+    fmt::Debug,
+    future::{Future, IntoFuture},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    commands::{CommandError, CommandTrait, Requirements},
+    manager, Command, SubsystemSUID,
+};
+
+type BoxedFuture = Pin<Box<dyn Future<Output = Result<(), CommandError>>>>;
+
+/// The output of an async command, either `()` or a `Result` whose error ends the command.
+pub trait AsyncOutput {
+    fn into_result(self) -> Result<(), CommandError>;
+}
+impl AsyncOutput for () {
+    fn into_result(self) -> Result<(), CommandError> {
+        Ok(())
+    }
+}
+impl<E: Into<CommandError>> AsyncOutput for Result<(), E> {
+    fn into_result(self) -> Result<(), CommandError> {
+        self.map_err(Into::into)
+    }
+}
+
+/// A command driven by a future, see [`Command::from_async`].
+pub struct AsyncCommand {
+    factory: Box<dyn FnMut() -> Option<BoxedFuture>>,
+    future: Option<BoxedFuture>,
+    finished: bool,
+    error: Option<CommandError>,
+    requirements: Vec<SubsystemSUID>,
+}
+impl Debug for AsyncCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncCommand")
+            .field("running", &self.future.is_some())
+            .field("finished", &self.finished)
+            .field("requirements", &self.requirements)
+            .finish_non_exhaustive()
+    }
+}
+impl AsyncCommand {
+    fn new<F>(mut factory: impl FnMut() -> Option<F> + 'static, subsystems: Requirements) -> Self
+    where
+        F: Future + 'static,
+        F::Output: AsyncOutput,
+    {
+        Self {
+            factory: Box::new(move || {
+                factory().map(|future| {
+                    Box::pin(async move { future.await.into_result() }) as BoxedFuture
+                })
+            }),
+            future: None,
+            finished: false,
+            error: None,
+            requirements: subsystems.iter().map(|s| s.suid()).collect(),
+        }
+    }
+}
+impl CommandTrait for AsyncCommand {
+    fn init(&mut self) {
+        self.future = (self.factory)();
+        self.finished = self.future.is_none();
+    }
+
+    fn periodic(&mut self, _: Duration) {
+        let Some(future) = self.future.as_mut() else {
+            return;
+        };
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(Ok(())) => {
+                self.future = None;
+                self.finished = true;
+            }
+            Poll::Ready(Err(error)) => {
+                self.future = None;
+                self.error = Some(error);
+            }
+            Poll::Pending => {}
+        }
+    }
+
+    fn end(&mut self, _interrupted: bool) {
+        // dropping the future cancels it, ending any command it was awaiting
+        self.future = None;
+    }
+
+    fn is_finished(&mut self) -> bool {
+        self.finished
+    }
+
+    fn take_error(&mut self) -> Option<CommandError> {
+        self.error.take()
+    }
+
+    fn get_requirements(&self) -> Vec<SubsystemSUID> {
+        self.requirements.clone()
+    }
+
+    fn get_name(&self) -> String {
+        String::from("AsyncCommand")
+    }
+}
+
+impl Command {
+    /// Creates a command that runs a future, polling it once per cycle until it completes.
+    ///
+    /// The future can only run once, scheduling the command again finishes it right away,
+    /// use [`Command::from_async_fn`] for commands that are scheduled more than once.
+    /// As the future could use any subsystem its requirements have to be given.
+    pub fn from_async<F>(future: F, subsystems: Requirements) -> Self
+    where
+        F: Future + 'static,
+        F::Output: AsyncOutput,
+    {
+        let mut future = Some(future);
+        Self::custom(Box::new(AsyncCommand::new(
+            move || future.take(),
+            subsystems,
+        )))
+    }
+
+    /// Creates a command that runs a new future from `factory` every time it is scheduled,
+    /// polling it once per cycle until it completes.
+    pub fn from_async_fn<F>(
+        mut factory: impl FnMut() -> F + 'static,
+        subsystems: Requirements,
+    ) -> Self
+    where
+        F: Future + 'static,
+        F::Output: AsyncOutput,
+    {
+        Self::custom(Box::new(AsyncCommand::new(
+            move || Some(factory()),
+            subsystems,
+        )))
+    }
+}
+
+/// Runs a command to completion when awaited inside an async command,
+/// see [`Command::into_future`](IntoFuture::into_future).
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct CommandFuture {
+    command: Command,
+    running: bool,
+    last_run: Option<Instant>,
+}
+impl Future for CommandFuture {
+    type Output = Result<(), CommandError>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let now = manager::now();
+        let dt = this.last_run.map_or(Duration::ZERO, |last_run| {
+            now.saturating_duration_since(last_run)
+        });
+        this.last_run = Some(now);
+        let command = &mut this.command;
+        if !this.running {
+            command.init();
+            this.running = true;
+            // a command that fails to init never runs
+            if let Some(error) = command.take_error() {
+                command.end(true);
+                this.running = false;
+                return Poll::Ready(Err(error));
+            }
+        }
+        command.periodic(dt);
+        if let Some(error) = command.take_error() {
+            command.end(true);
+            this.running = false;
+            return Poll::Ready(Err(error));
+        }
+        if command.is_finished() {
+            command.end(false);
+            this.running = false;
+            return Poll::Ready(Ok(()));
+        }
+        Poll::Pending
+    }
+}
+impl Drop for CommandFuture {
+    fn drop(&mut self) {
+        if self.running {
+            self.command.end(true);
+        }
+    }
+}
+impl IntoFuture for Command {
+    type Output = Result<(), CommandError>;
+    type IntoFuture = CommandFuture;
+
+    /// Awaiting a command inside an async command runs it inline until it finishes,
+    /// resolving to the command's error if it failed, use `?` to end the async command with it.
+    fn into_future(self) -> CommandFuture {
+        CommandFuture {
+            command: self,
+            running: false,
+            last_run: None,
+        }
+    }
+}
+
+/// Completes after the given duration, measured in scheduler cycles from the first poll.
+pub async fn wait(duration: Duration) {
+    let deadline = manager::now() + duration;
+    wait_until(|| manager::now() >= deadline).await;
+}
+
+/// Completes on the first poll `condition` returns true.
+pub async fn wait_until(mut condition: impl FnMut() -> bool) {
+    while !condition() {
+        yield_now().await;
+    }
+}
+
+/// Yields until the next cycle.
+pub async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|_| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+    .await;
+}
@@ -0,0 +1,450 @@
+//! Human interface device (gamepad) abstractions that produce [`Condition`]s.
+//!
+//! Input is read through the [`InputSource`] trait so that tests and simulation
+//! can feed scripted values through a [`SimInput`] without any hardware.
+
+use std::{cell::RefCell, fmt::Debug, ops::Deref, rc::Rc};
+
+use crate::conditions::Condition;
+
+/// A snapshot of a controller's inputs at a single point in time.
+///
+/// Buttons are 1-indexed to match the driver station,
+/// axes and POVs are 0-indexed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControllerState {
+    /// A bitmask of pressed buttons, bit `n - 1` is button `n`.
+    pub buttons: u32,
+    /// The value of each axis, usually in the range `[-1.0, 1.0]`.
+    pub axes: Vec<f64>,
+    /// The angle of the POV hat in degrees, `None` if the hat is centered.
+    pub pov: Option<u16>,
+}
+impl ControllerState {
+    /// Returns true if the given 1-indexed button is pressed.
+    #[must_use]
+    pub const fn button(&self, button: u8) -> bool {
+        button != 0 && button <= 32 && self.buttons & (1 << (button - 1)) != 0
+    }
+
+    /// Returns the value of the given axis, or `0.0` if the axis does not exist.
+    #[must_use]
+    pub fn axis(&self, axis: usize) -> f64 {
+        self.axes.get(axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// A source of controller input, usually backed by the driver station.
+///
+/// Conditions read single inputs through [`button`](InputSource::button), [`axis`](InputSource::axis)
+/// and [`pov`](InputSource::pov) every cycle, sources that can read them without building
+/// a whole [`ControllerState`] should override them.
+pub trait InputSource {
+    /// Returns the current state of the controller.
+    fn state(&self) -> ControllerState;
+
+    /// Returns true if the given 1-indexed button is pressed.
+    fn button(&self, button: u8) -> bool {
+        self.state().button(button)
+    }
+
+    /// Returns the value of the given axis, or `0.0` if the axis does not exist.
+    fn axis(&self, axis: usize) -> f64 {
+        self.state().axis(axis)
+    }
+
+    /// Returns the angle of the POV hat in degrees, `None` if the hat is centered.
+    fn pov(&self) -> Option<u16> {
+        self.state().pov
+    }
+}
+impl<F: Fn() -> ControllerState> InputSource for F {
+    fn state(&self) -> ControllerState {
+        self()
+    }
+}
+
+/// An [`InputSource`] whose values are set by hand, meant for tests and simulation.
+///
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct SimInput {
+    state: Rc<RefCell<ControllerState>>,
+}
+impl SimInput {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the given 1-indexed button is pressed.
+    pub fn set_button(&self, button: u8, pressed: bool) {
+        if button == 0 || button > 32 {
+            return;
+        }
+        let mask = 1 << (button - 1);
+        let mut state = self.state.borrow_mut();
+        if pressed {
+            state.buttons |= mask;
+        } else {
+            state.buttons &= !mask;
+        }
+    }
+
+    /// Sets the value of the given axis, growing the axis list if needed.
+    pub fn set_axis(&self, axis: usize, value: f64) {
+        let mut state = self.state.borrow_mut();
+        if state.axes.len() <= axis {
+            state.axes.resize(axis + 1, 0.0);
+        }
+        state.axes[axis] = value;
+    }
+
+    /// Sets the angle of the POV hat, `None` to center it.
+    pub fn set_pov(&self, pov: Option<u16>) {
+        self.state.borrow_mut().pov = pov;
+    }
+
+    /// Replaces the whole controller state.
+    pub fn set_state(&self, state: ControllerState) {
+        *self.state.borrow_mut() = state;
+    }
+}
+impl InputSource for SimInput {
+    fn state(&self) -> ControllerState {
+        self.state.borrow().clone()
+    }
+
+    fn button(&self, button: u8) -> bool {
+        self.state.borrow().button(button)
+    }
+
+    fn axis(&self, axis: usize) -> f64 {
+        self.state.borrow().axis(axis)
+    }
+
+    fn pov(&self) -> Option<u16> {
+        self.state.borrow().pov
+    }
+}
+
+/// A generic controller with no knowledge of its layout.
+///
+/// Cheap to clone, clones share the same [`InputSource`].
+#[derive(Clone)]
+pub struct Controller {
+    source: Rc<dyn InputSource>,
+}
+impl Debug for Controller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Controller")
+            .field("source", &"dyn InputSource")
+            .finish()
+    }
+}
+impl Controller {
+    pub fn new(source: impl InputSource + 'static) -> Self {
+        Self {
+            source: Rc::new(source),
+        }
+    }
+
+    /// Returns the current state of the controller.
+    #[must_use]
+    pub fn state(&self) -> ControllerState {
+        self.source.state()
+    }
+
+    /// Returns the current value of the given axis.
+    #[must_use]
+    pub fn get_axis(&self, axis: usize) -> f64 {
+        self.source.axis(axis)
+    }
+
+    /// Returns a condition that is true while the given 1-indexed button is pressed.
+    #[must_use]
+    pub fn button(&self, button: u8) -> Condition {
+        let source = self.source.clone();
+        Condition::new(move || source.button(button))
+    }
+
+    /// Returns a condition that is true while the given axis is greater than `threshold`.
+    #[must_use]
+    pub fn axis_greater_than(&self, axis: usize, threshold: f64) -> Condition {
+        let source = self.source.clone();
+        Condition::new(move || source.axis(axis) > threshold)
+    }
+
+    /// Returns a condition that is true while the given axis is less than `threshold`.
+    #[must_use]
+    pub fn axis_less_than(&self, axis: usize, threshold: f64) -> Condition {
+        let source = self.source.clone();
+        Condition::new(move || source.axis(axis) < threshold)
+    }
+
+    /// Returns a condition that is true while the POV hat is at the given angle in degrees.
+    #[must_use]
+    pub fn pov(&self, angle: u16) -> Condition {
+        let source = self.source.clone();
+        Condition::new(move || source.pov() == Some(angle))
+    }
+
+    #[must_use]
+    pub fn pov_up(&self) -> Condition {
+        self.pov(0)
+    }
+
+    #[must_use]
+    pub fn pov_right(&self) -> Condition {
+        self.pov(90)
+    }
+
+    #[must_use]
+    pub fn pov_down(&self) -> Condition {
+        self.pov(180)
+    }
+
+    #[must_use]
+    pub fn pov_left(&self) -> Condition {
+        self.pov(270)
+    }
+
+    /// Returns a condition that is true while the POV hat is not pressed.
+    #[must_use]
+    pub fn pov_center(&self) -> Condition {
+        let source = self.source.clone();
+        Condition::new(move || source.pov().is_none())
+    }
+}
+
+/// An Xbox controller, using the driver station's button and axis layout.
+#[derive(Debug, Clone)]
+pub struct XboxController(Controller);
+impl XboxController {
+    pub const LEFT_X: usize = 0;
+    pub const LEFT_Y: usize = 1;
+    pub const LEFT_TRIGGER: usize = 2;
+    pub const RIGHT_TRIGGER: usize = 3;
+    pub const RIGHT_X: usize = 4;
+    pub const RIGHT_Y: usize = 5;
+
+    pub fn new(source: impl InputSource + 'static) -> Self {
+        Self(Controller::new(source))
+    }
+
+    #[must_use]
+    pub fn a(&self) -> Condition {
+        self.button(1)
+    }
+
+    #[must_use]
+    pub fn b(&self) -> Condition {
+        self.button(2)
+    }
+
+    #[must_use]
+    pub fn x(&self) -> Condition {
+        self.button(3)
+    }
+
+    #[must_use]
+    pub fn y(&self) -> Condition {
+        self.button(4)
+    }
+
+    #[must_use]
+    pub fn left_bumper(&self) -> Condition {
+        self.button(5)
+    }
+
+    #[must_use]
+    pub fn right_bumper(&self) -> Condition {
+        self.button(6)
+    }
+
+    #[must_use]
+    pub fn back(&self) -> Condition {
+        self.button(7)
+    }
+
+    #[must_use]
+    pub fn start(&self) -> Condition {
+        self.button(8)
+    }
+
+    #[must_use]
+    pub fn left_stick(&self) -> Condition {
+        self.button(9)
+    }
+
+    #[must_use]
+    pub fn right_stick(&self) -> Condition {
+        self.button(10)
+    }
+
+    /// Returns a condition that is true while the left trigger is past `threshold`.
+    #[must_use]
+    pub fn left_trigger(&self, threshold: f64) -> Condition {
+        self.axis_greater_than(Self::LEFT_TRIGGER, threshold)
+    }
+
+    /// Returns a condition that is true while the right trigger is past `threshold`.
+    #[must_use]
+    pub fn right_trigger(&self, threshold: f64) -> Condition {
+        self.axis_greater_than(Self::RIGHT_TRIGGER, threshold)
+    }
+
+    #[must_use]
+    pub fn left_x(&self) -> f64 {
+        self.get_axis(Self::LEFT_X)
+    }
+
+    #[must_use]
+    pub fn left_y(&self) -> f64 {
+        self.get_axis(Self::LEFT_Y)
+    }
+
+    #[must_use]
+    pub fn right_x(&self) -> f64 {
+        self.get_axis(Self::RIGHT_X)
+    }
+
+    #[must_use]
+    pub fn right_y(&self) -> f64 {
+        self.get_axis(Self::RIGHT_Y)
+    }
+}
+impl Deref for XboxController {
+    type Target = Controller;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A PS4 controller, using the driver station's button and axis layout.
+#[derive(Debug, Clone)]
+pub struct Ps4Controller(Controller);
+impl Ps4Controller {
+    pub const LEFT_X: usize = 0;
+    pub const LEFT_Y: usize = 1;
+    pub const RIGHT_X: usize = 2;
+    pub const L2: usize = 3;
+    pub const R2: usize = 4;
+    pub const RIGHT_Y: usize = 5;
+
+    pub fn new(source: impl InputSource + 'static) -> Self {
+        Self(Controller::new(source))
+    }
+
+    #[must_use]
+    pub fn square(&self) -> Condition {
+        self.button(1)
+    }
+
+    #[must_use]
+    pub fn cross(&self) -> Condition {
+        self.button(2)
+    }
+
+    #[must_use]
+    pub fn circle(&self) -> Condition {
+        self.button(3)
+    }
+
+    #[must_use]
+    pub fn triangle(&self) -> Condition {
+        self.button(4)
+    }
+
+    #[must_use]
+    pub fn l1(&self) -> Condition {
+        self.button(5)
+    }
+
+    #[must_use]
+    pub fn r1(&self) -> Condition {
+        self.button(6)
+    }
+
+    /// Returns a condition that is true while the L2 button is fully pressed,
+    /// see [`left_trigger`](Ps4Controller::left_trigger) for an analog threshold.
+    #[must_use]
+    pub fn l2(&self) -> Condition {
+        self.button(7)
+    }
+
+    /// Returns a condition that is true while the R2 button is fully pressed,
+    /// see [`right_trigger`](Ps4Controller::right_trigger) for an analog threshold.
+    #[must_use]
+    pub fn r2(&self) -> Condition {
+        self.button(8)
+    }
+
+    #[must_use]
+    pub fn share(&self) -> Condition {
+        self.button(9)
+    }
+
+    #[must_use]
+    pub fn options(&self) -> Condition {
+        self.button(10)
+    }
+
+    #[must_use]
+    pub fn l3(&self) -> Condition {
+        self.button(11)
+    }
+
+    #[must_use]
+    pub fn r3(&self) -> Condition {
+        self.button(12)
+    }
+
+    #[must_use]
+    pub fn ps(&self) -> Condition {
+        self.button(13)
+    }
+
+    #[must_use]
+    pub fn touchpad(&self) -> Condition {
+        self.button(14)
+    }
+
+    /// Returns a condition that is true while the L2 axis is past `threshold`.
+    #[must_use]
+    pub fn left_trigger(&self, threshold: f64) -> Condition {
+        self.axis_greater_than(Self::L2, threshold)
+    }
+
+    /// Returns a condition that is true while the R2 axis is past `threshold`.
+    #[must_use]
+    pub fn right_trigger(&self, threshold: f64) -> Condition {
+        self.axis_greater_than(Self::R2, threshold)
+    }
+
+    #[must_use]
+    pub fn left_x(&self) -> f64 {
+        self.get_axis(Self::LEFT_X)
+    }
+
+    #[must_use]
+    pub fn left_y(&self) -> f64 {
+        self.get_axis(Self::LEFT_Y)
+    }
+
+    #[must_use]
+    pub fn right_x(&self) -> f64 {
+        self.get_axis(Self::RIGHT_X)
+    }
+
+    #[must_use]
+    pub fn right_y(&self) -> f64 {
+        self.get_axis(Self::RIGHT_Y)
+    }
+}
+impl Deref for Ps4Controller {
+    type Target = Controller;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
@@ -27,20 +27,48 @@
 //!   }
 //! }
 //! ```
+//!
+//! ## Running the command manager at a fixed rate
+//! Sleeping a fixed amount after every run drifts by however long the run took,
+//! [`RobotRunner`](robot::RobotRunner) keeps the loop on schedule.
+//! ```
+//! use frclib_commands::{robot::{Robot, RobotRunner}, CommandManager};
+//! use std::time::Duration;
+//!
+//! struct MyRobot;
+//! impl Robot for MyRobot {}
+//!
+//! let mut runner = RobotRunner::new(CommandManager::new(), MyRobot)
+//!     .with_period(Duration::from_millis(95));
+//! runner.run_for(Duration::from_millis(950));
+//! ```
 
 //TODO: when frclib-core is public this has to rely on that for its time
 
 
 #[macro_use]
 pub mod manager;
+pub mod behavior_tree;
 pub mod commands;
 pub mod conditions;
+pub mod diagram;
+pub mod future;
+pub mod hid;
+#[cfg(feature = "nt")]
+pub mod nt;
+pub mod robot;
+pub mod snapshot;
 #[cfg(test)]
 mod test;
+pub mod watchdog;
+pub mod wpilog;
 
 pub use commands::*;
 pub use manager::*;
 
+#[cfg(feature = "derive")]
+pub use frclib_commands_derive::Subsystem;
+
 #[derive(Debug, Clone, Copy)]
 pub struct WrongThreadError(&'static str);
 impl std::fmt::Display for WrongThreadError {
@@ -113,3 +141,77 @@ macro_rules! clone_mv {
         }
     };
 }
+
+/// Builds a sequential group, see [`Command::sequential`].
+/// Each item can be anything that converts into a [`Command`].
+///
+/// # Examples
+/// ```
+/// use frclib_commands::{parallel, sequence, Command, CommandBuilder};
+/// use std::time::Duration;
+///
+/// let auto: Command = sequence![
+///     CommandBuilder::new().init(|| println!("Drive to note")),
+///     parallel![
+///         Command::wait_for(Duration::from_millis(500)),
+///         CommandBuilder::new().is_finished(|| true).build(),
+///     ],
+///     Command::empty(),
+/// ];
+/// ```
+#[macro_export]
+macro_rules! sequence {
+    ($($command:expr),* $(,)?) => {
+        $crate::Command::sequential(vec![$($crate::Command::from($command)),*])
+    };
+}
+
+/// Builds a parallel group that finishes when all of its members finish, see [`Command::parallel`].
+/// Each item can be anything that converts into a [`Command`].
+///
+/// # Panics
+/// If any of the members require the same subsystem.
+#[macro_export]
+macro_rules! parallel {
+    ($($command:expr),* $(,)?) => {
+        $crate::Command::parallel(vec![$($crate::Command::from($command)),*])
+    };
+}
+
+/// Builds a parallel group that finishes when any of its members finish, see [`Command::race`].
+/// Each item can be anything that converts into a [`Command`].
+///
+/// # Panics
+/// If any of the members require the same subsystem.
+#[macro_export]
+macro_rules! race {
+    ($($command:expr),* $(,)?) => {
+        $crate::Command::race(vec![$($crate::Command::from($command)),*])
+    };
+}
+
+/// Builds a parallel group that finishes when its first member finishes, see [`Command::deadline`].
+/// Each item can be anything that converts into a [`Command`].
+///
+/// # Panics
+/// If any of the members require the same subsystem.
+///
+/// # Examples
+/// ```
+/// use frclib_commands::{deadline, Command, CommandBuilder};
+/// use std::time::Duration;
+///
+/// let intake_while_driving: Command = deadline!(
+///     Command::wait_for(Duration::from_secs(2)),
+///     CommandBuilder::new().periodic(|_period| println!("Intaking")),
+/// );
+/// ```
+#[macro_export]
+macro_rules! deadline {
+    ($deadline:expr $(, $command:expr)* $(,)?) => {
+        $crate::Command::deadline(
+            $crate::Command::from($deadline),
+            vec![$($crate::Command::from($command)),*],
+        )
+    };
+}
@@ -1,12 +1,21 @@
 use std::{
-    cell::{RefCell, UnsafeCell},
+    cell::{Cell, RefCell, UnsafeCell},
     collections::{HashMap, HashSet},
     hash::{Hash, Hasher},
     ops::{Deref, DerefMut},
+    rc::Rc,
     time::{Duration, Instant},
 };
 
-use super::{commands::CommandTrait, conditions::ConditionalScheduler, Command, WrongThreadError};
+use super::{
+    commands::{CommandError, CommandTrait, InterruptionBehavior, Requirement},
+    conditions::{Condition, ConditionalScheduler, EventLoop},
+    robot::RobotMode,
+    snapshot::{BindingSnapshot, CommandSnapshot, SchedulerSnapshot, SubsystemSnapshot},
+    watchdog::{EpochKind, LoopStats, Watchdog},
+    wpilog::{self, DataLog},
+    Command, WrongThreadError,
+};
 
 pub type SubsystemSUID = u64;
 
@@ -15,7 +24,17 @@ struct ManagerQueue {
     cond_queue: Vec<ConditionalScheduler>,
 }
 thread_local! {
-    static MANAGER_QUEUE: RefCell<Option<ManagerQueue>> = RefCell::new(None);
+    static MANAGER_QUEUE: RefCell<Option<ManagerQueue>> = const { RefCell::new(None) };
+    static CYCLE_START: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// The time the current scheduler cycle started at.
+///
+/// Everything polled during the same [`CommandManager::run`] sees the same instant,
+/// outside of a run (or on a thread without a command manager) this is [`Instant::now`].
+#[must_use]
+pub fn now() -> Instant {
+    CYCLE_START.with(Cell::get).unwrap_or_else(Instant::now)
 }
 
 /// Puts a command in the queue to be scheduled next time the scheduler runs
@@ -102,7 +121,7 @@ pub struct SubsystemCell<T: Subsystem + 'static>(&'static UnsafeCell<T>);
 
 impl<T: Subsystem + 'static> Clone for SubsystemCell<T> {
     fn clone(&self) -> Self {
-        Self(self.0)
+        *self
     }
 }
 impl<T: Subsystem + 'static> Copy for SubsystemCell<T> {}
@@ -167,7 +186,30 @@ pub enum CommandManagerError {
     SubsystemAlreadyRegistered,
 }
 
+/// The result of scheduling a command, commands that are not scheduled are dropped.
+#[must_use]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScheduleOutcome {
+    /// The command will start on the next [`run`](CommandManager::run).
+    Scheduled(CommandIndex),
+    /// A running command requiring the same subsystems has a higher priority
+    /// or an equal priority and [`InterruptionBehavior::CancelIncoming`](crate::InterruptionBehavior::CancelIncoming).
+    RejectedBy(CommandIndex),
+    /// The command is already running, it keeps running undisturbed
+    /// unless it was already going to be interrupted on the next [`run`](CommandManager::run).
+    AlreadyRunning(CommandIndex),
+    /// The robot is disabled and the command does not [`run_when_disabled`](CommandTrait::run_when_disabled).
+    Disabled,
+}
+impl ScheduleOutcome {
+    #[must_use]
+    pub const fn is_scheduled(&self) -> bool {
+        matches!(self, Self::Scheduled(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CommandIndex {
     DefaultCommand(usize),
     Command(usize),
@@ -176,8 +218,47 @@ pub enum CommandIndex {
 
 use fxhash::{FxHashMap, FxHashSet};
 
+/// Identifies a callback added with [`CommandManager::add_periodic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeriodicId(u64);
+
+type ErrorHandler = Box<dyn FnMut(&str, &CommandError)>;
+
+struct PeriodicCallback {
+    /// `None` for callbacks owned by the command manager, like subsystem periodics.
+    id: Option<PeriodicId>,
+    name: String,
+    callback: Box<dyn FnMut(Duration)>,
+    last_run: Option<Instant>,
+    /// `None` for callbacks run once every [`CommandManager::run`], like subsystem periodics.
+    period: Option<Duration>,
+    next_run: Instant,
+}
+impl PeriodicCallback {
+    fn is_due(&self, now: Instant) -> bool {
+        self.period.is_none() || now >= self.next_run
+    }
+}
+
+/// Scheduler state shared with the conditions created by the command manager's event factories.
+#[derive(Debug, Default)]
+struct SchedulerEvents {
+    finished: RefCell<FxHashSet<String>>,
+    interrupted: RefCell<FxHashSet<String>>,
+    busy_subsystems: RefCell<FxHashSet<SubsystemSUID>>,
+    match_time: Cell<Option<(Instant, Duration)>>,
+}
+impl SchedulerEvents {
+    fn match_time_remaining(&self) -> Option<Duration> {
+        self.match_time.get().map(|(set_at, remaining)| {
+            remaining.saturating_sub(now().saturating_duration_since(set_at))
+        })
+    }
+}
+
 pub struct CommandManager {
-    periodic_callbacks: Vec<(Box<dyn FnMut(Duration)>, Option<Instant>)>,
+    periodic_callbacks: Vec<PeriodicCallback>,
+    next_periodic_id: u64,
     commands: Vec<Option<Command>>,
     default_commands: Vec<Option<Command>>,
     preserved_commands: Vec<Option<Command>>,
@@ -187,6 +268,22 @@ pub struct CommandManager {
     initialized_commands: FxHashSet<CommandIndex>,
     orphaned_commands: FxHashSet<CommandIndex>,
     cond_schedulers: Vec<ConditionalScheduler>,
+    event_loops: FxHashMap<EventLoop, Vec<ConditionalScheduler>>,
+    attached_event_loops: FxHashSet<EventLoop>,
+    events: Rc<SchedulerEvents>,
+    subsystems: Vec<(SubsystemSUID, &'static str)>,
+    init_times: FxHashMap<CommandIndex, Instant>,
+    /// The names commands had when they were scheduled, the event conditions match on these.
+    scheduled_names: FxHashMap<CommandIndex, String>,
+    /// An id unique to every time a command was scheduled, unlike indices which are reused.
+    schedule_ids: FxHashMap<CommandIndex, u64>,
+    next_schedule_id: u64,
+    logged_owners: FxHashMap<SubsystemSUID, Option<CommandIndex>>,
+    watchdog: Watchdog,
+    mode: Option<RobotMode>,
+    catch_panics: bool,
+    command_errors: FxHashMap<CommandIndex, CommandError>,
+    error_handler: Option<ErrorHandler>,
 }
 impl CommandManager {
     #[must_use]
@@ -199,6 +296,7 @@ impl CommandManager {
         });
         Self {
             periodic_callbacks: Vec::new(),
+            next_periodic_id: 0,
             commands: Vec::new(),
             default_commands: Vec::new(),
             preserved_commands: Vec::new(),
@@ -208,6 +306,20 @@ impl CommandManager {
             initialized_commands: HashSet::with_hasher(fxhash::FxBuildHasher::default()),
             orphaned_commands: HashSet::with_hasher(fxhash::FxBuildHasher::default()),
             cond_schedulers: Vec::new(),
+            event_loops: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            attached_event_loops: HashSet::with_hasher(fxhash::FxBuildHasher::default()),
+            events: Rc::default(),
+            subsystems: Vec::new(),
+            init_times: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            scheduled_names: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            schedule_ids: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            next_schedule_id: 0,
+            logged_owners: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            watchdog: Watchdog::default(),
+            mode: None,
+            catch_panics: false,
+            command_errors: HashMap::with_hasher(fxhash::FxBuildHasher::default()),
+            error_handler: None,
         }
     }
 
@@ -224,18 +336,26 @@ impl CommandManager {
             return Err(CommandManagerError::SubsystemAlreadyRegistered);
         }
         let immortal_mut = unsafe { subsystem.immortal_mut() };
-        self.periodic_callbacks.push((
-            Box::new(move |dt| unsafe {
-                (&mut *immortal_mut).periodic(dt);
+        let name = subsystem.name();
+        self.periodic_callbacks.push(PeriodicCallback {
+            id: None,
+            name: name.to_owned(),
+            callback: Box::new(move |dt| unsafe {
+                let _span = tracing::trace_span!("subsystem", name).entered();
+                tracing::trace_span!("periodic").in_scope(|| (*immortal_mut).periodic(dt));
+                tracing::trace_span!("log").in_scope(|| (*immortal_mut).log());
             }),
-            None,
-        ));
+            last_run: None,
+            period: None,
+            next_run: Instant::now(),
+        });
         self.default_commands.push(default_command);
         let idx = self.default_commands.len() - 1;
         self.subsystem_to_default
             .insert(subsystem.suid(), CommandIndex::DefaultCommand(idx));
         self.interrupt_state
             .insert(CommandIndex::DefaultCommand(idx), false);
+        self.subsystems.push((subsystem.suid(), subsystem.name()));
         tracing::debug!("Registered subsystem: {}", subsystem.name());
         Ok(())
     }
@@ -254,78 +374,177 @@ impl CommandManager {
         }
     }
 
-    fn get_command(&mut self, index: CommandIndex) -> Option<&mut Command> {
+    fn get_command_ref(&self, index: CommandIndex) -> Option<&Command> {
         match index {
-            CommandIndex::Command(idx) => self.commands.get_mut(idx).and_then(Option::as_mut),
+            CommandIndex::Command(idx) => self.commands.get(idx).and_then(Option::as_ref),
             CommandIndex::DefaultCommand(idx) => {
-                self.default_commands.get_mut(idx).and_then(Option::as_mut)
+                self.default_commands.get(idx).and_then(Option::as_ref)
+            }
+            CommandIndex::PreservedCommand(idx) => {
+                self.preserved_commands.get(idx).and_then(Option::as_ref)
             }
-            CommandIndex::PreservedCommand(idx) => self
-                .preserved_commands
-                .get_mut(idx)
-                .and_then(Option::as_mut),
         }
     }
 
-    pub fn schedule(&mut self, command: Command) {
+    /// Every command index that has to be polled this cycle, each index appears once.
+    fn active_commands(&self) -> Vec<CommandIndex> {
+        let mut seen = FxHashSet::default();
+        // interrupted commands no longer hold their requirements but still need to be ended
+        self.requirements
+            .values()
+            .chain(self.orphaned_commands.iter())
+            .chain(
+                self.interrupt_state
+                    .iter()
+                    .filter_map(|(index, interrupted)| interrupted.then_some(index)),
+            )
+            .copied()
+            .filter(|index| seen.insert(*index))
+            .collect()
+    }
+
+    /// Schedules a command to start on the next [`run`](CommandManager::run),
+    /// interrupting any running commands it preempts.
+    pub fn schedule(&mut self, command: Command) -> ScheduleOutcome {
         let index = self.add_command(command);
-        self.inner_schedule(index);
+        self.inner_schedule(index)
     }
 
-    fn inner_schedule(&mut self, index: CommandIndex) {
-        let req = &self
-            .get_command(index)
-            .expect("Internal State Error: Command not found")
-            .get_requirements()[..];
+    fn is_running(&self, index: CommandIndex) -> bool {
+        self.interrupt_state.get(&index) == Some(&false)
+            && (self.orphaned_commands.contains(&index)
+                || self.requirements.values().any(|idx| *idx == index))
+    }
+
+    fn inner_schedule(&mut self, index: CommandIndex) -> ScheduleOutcome {
+        if self.is_running(index) {
+            return ScheduleOutcome::AlreadyRunning(index);
+        }
+        // a pending interrupt still has to end the command before it can be initialized again
+        if self.interrupt_state.get(&index) == Some(&true) {
+            return ScheduleOutcome::AlreadyRunning(index);
+        }
+        let incoming = self
+            .get_command_ref(index)
+            .expect("Internal State Error: Command not found");
+        if self.mode == Some(RobotMode::Disabled) && !incoming.run_when_disabled() {
+            tracing::debug!("{} was not scheduled while disabled", incoming.get_name());
+            self.discard(index);
+            return ScheduleOutcome::Disabled;
+        }
+        let req = incoming.get_requirements();
+        let priority = incoming.priority();
         if req.is_empty() {
+            self.mark_scheduled(index, incoming.get_name());
+            self.command_errors.remove(&index);
             self.orphaned_commands.insert(index);
-        } else {
-            let mut can_cancel = true;
-            let mut to_cancel = HashSet::with_capacity(req.len());
-            for requirement in req {
-                if let Some(index) = self.requirements.get_mut(requirement) {
-                    let index = *index;
-                    if let Some(cmd) = self.get_command(index) {
-                        if can_cancel && cmd.cancel_incoming() {
-                            can_cancel = false;
-                            break;
-                        }
-                        to_cancel.insert(index);
+            return ScheduleOutcome::Scheduled(index);
+        }
+        let mut to_cancel = HashSet::with_capacity(req.len());
+        for requirement in &req {
+            let Some(running_idx) = self
+                .requirements
+                .get(requirement)
+                .copied()
+                .filter(|running_idx| *running_idx != index)
+            else {
+                continue;
+            };
+            let Some(running) = self.get_command_ref(running_idx) else {
+                continue;
+            };
+            // default commands always make way for other commands
+            let preempts = matches!(running_idx, CommandIndex::DefaultCommand(_))
+                || match priority.cmp(&running.priority()) {
+                    std::cmp::Ordering::Greater => true,
+                    std::cmp::Ordering::Less => false,
+                    std::cmp::Ordering::Equal => {
+                        running.interruption_behavior() == InterruptionBehavior::CancelSelf
                     }
-                }
-            }
-            if can_cancel {
-                for index in to_cancel {
-                    self.interrupt_state.insert(index, true);
-                }
-                for requirement in req {
-                    self.requirements.insert(*requirement, index);
-                }
-                self.interrupt_state.insert(index, false);
+                };
+            if !preempts {
+                tracing::debug!(
+                    "{} was rejected by {}",
+                    incoming.get_name(),
+                    running.get_name()
+                );
+                self.discard(index);
+                return ScheduleOutcome::RejectedBy(running_idx);
             }
+            to_cancel.insert(running_idx);
+        }
+        self.mark_scheduled(index, incoming.get_name());
+        for index in to_cancel {
+            self.interrupt_state.insert(index, true);
+        }
+        for requirement in req {
+            self.requirements.insert(requirement, index);
+        }
+        self.interrupt_state.insert(index, false);
+        self.command_errors.remove(&index);
+        ScheduleOutcome::Scheduled(index)
+    }
+
+    /// Schedules a command no caller is waiting on the outcome of, like queued and bound commands,
+    /// so rejections are traced instead of returned.
+    fn schedule_unobserved(&mut self, index: CommandIndex, source: &str) {
+        let outcome = self.inner_schedule(index);
+        if matches!(
+            outcome,
+            ScheduleOutcome::RejectedBy(_) | ScheduleOutcome::Disabled
+        ) {
+            tracing::debug!("{source} command {index:?} was not scheduled: {outcome:?}");
         }
     }
 
+    /// Records the name and a new schedule id of a command that was just scheduled.
+    fn mark_scheduled(&mut self, index: CommandIndex, name: String) {
+        self.scheduled_names.insert(index, name);
+        self.schedule_ids.insert(index, self.next_schedule_id);
+        self.next_schedule_id += 1;
+    }
+
+    /// Frees the slot of a command that was never scheduled,
+    /// commands owned by bindings or subsystems are kept to be scheduled again.
+    fn discard(&mut self, index: CommandIndex) {
+        if let CommandIndex::Command(idx) = index {
+            self.interrupt_state.remove(&index);
+            self.commands[idx] = None;
+        }
+    }
+
+    /// Cancels a running command, it will have `end(true)` called on the next [`run`](CommandManager::run).
+    ///
+    /// Returns false if no command is running at the given index.
+    pub fn cancel(&mut self, index: CommandIndex) -> bool {
+        if self.get_command_ref(index).is_none() || !self.is_running(index) {
+            return false;
+        }
+        self.interrupt_state.insert(index, true);
+        true
+    }
+
     pub(crate) fn remove_command(&mut self, command_idx: CommandIndex) {
         self.initialized_commands.remove(&command_idx);
-        self.interrupt_state.remove(&command_idx);
-
-        let command = match self.get_command(command_idx) {
-            Some(command) => command,
-            None => return,
-        };
-        let requirements = command.get_requirements();
+        self.init_times.remove(&command_idx);
+        self.scheduled_names.remove(&command_idx);
+        self.schedule_ids.remove(&command_idx);
         if let CommandIndex::Command(idx) = command_idx {
-            self.commands[idx] = None;
+            self.interrupt_state.remove(&command_idx);
+            if let Some(slot) = self.commands.get_mut(idx) {
+                *slot = None;
+            }
+        } else {
+            // default and preserved commands are kept around to be scheduled again
+            self.interrupt_state.insert(command_idx, false);
         }
         self.orphaned_commands.remove(&command_idx);
-        requirements.iter().for_each(|req| {
-            self.requirements.remove(req);
-        });
+        // only release requirements this command still holds, an interrupting command may own them now
+        self.requirements.retain(|_, idx| *idx != command_idx);
     }
 
     pub(crate) fn add_cond_scheduler(&mut self, mut scheduler: ConditionalScheduler) {
-        if let Some(idx) = self.commands.iter().position(Option::is_none) {
+        if let Some(idx) = self.preserved_commands.iter().position(Option::is_none) {
             let index = CommandIndex::PreservedCommand(idx);
             self.interrupt_state.insert(index, false);
             let cmd = scheduler.exchange(index);
@@ -336,11 +555,332 @@ impl CommandManager {
             let cmd = scheduler.exchange(index);
             self.preserved_commands.push(Some(cmd));
         }
-        self.cond_schedulers.push(scheduler);
+        match scheduler.event_loop() {
+            Some(event_loop) => self
+                .event_loops
+                .entry(event_loop)
+                .or_default()
+                .push(scheduler),
+            None => self.cond_schedulers.push(scheduler),
+        }
+    }
+
+    /// Installs a data log on this thread, the command manager will log command events,
+    /// subsystem ownership changes and loop timing to it.
+    /// Subsystems can append their own entries through [`wpilog::append`].
+    ///
+    /// Returns the previously installed data log, if any.
+    pub fn set_data_log(&mut self, data_log: DataLog) -> Option<DataLog> {
+        self.logged_owners.clear();
+        wpilog::install(Some(data_log))
+    }
+
+    /// Removes the data log installed on this thread, if any.
+    pub fn take_data_log(&mut self) -> Option<DataLog> {
+        wpilog::install(None)
+    }
+
+    /// Sets the time a single [`run`](CommandManager::run) is expected to take, usually 20ms.
+    /// While a budget is set the time spent in every phase, periodic callback and command is measured,
+    /// cycles that go over budget are reported through `tracing` with their slowest parts.
+    ///
+    /// Setting the budget resets the [`loop_stats`](CommandManager::loop_stats),
+    /// `None` disables the measurements.
+    pub fn set_loop_budget(&mut self, budget: Option<Duration>) {
+        self.watchdog.set_budget(budget);
+    }
+
+    /// Returns the loop timing statistics collected since the loop budget was set.
+    #[must_use]
+    pub const fn loop_stats(&self) -> &LoopStats {
+        self.watchdog.stats()
+    }
+
+    /// Adds a callback that is run every `period`, starting `offset` from now.
+    /// The callback is given the time since it last started, or zero on its first run.
+    ///
+    /// Callbacks are run at most once per [`run`](CommandManager::run),
+    /// a [`RobotRunner`](crate::robot::RobotRunner) also runs them between cycles
+    /// so they can be faster than the main loop.
+    /// A zero `period` runs the callback once every [`run`](CommandManager::run).
+    pub fn add_periodic(
+        &mut self,
+        callback: impl FnMut(Duration) + 'static,
+        period: Duration,
+        offset: Duration,
+    ) -> PeriodicId {
+        let name = format!("periodic {}", self.next_periodic_id);
+        self.add_named_periodic(name, callback, period, offset)
+    }
+
+    /// Same as [`add_periodic`](CommandManager::add_periodic)
+    /// but with a name used in traces and loop timing reports.
+    pub fn add_named_periodic(
+        &mut self,
+        name: impl Into<String>,
+        callback: impl FnMut(Duration) + 'static,
+        period: Duration,
+        offset: Duration,
+    ) -> PeriodicId {
+        let id = PeriodicId(self.next_periodic_id);
+        self.next_periodic_id += 1;
+        self.periodic_callbacks.push(PeriodicCallback {
+            id: Some(id),
+            name: name.into(),
+            callback: Box::new(callback),
+            last_run: None,
+            period: Some(period),
+            next_run: Instant::now() + offset,
+        });
+        id
+    }
+
+    /// Removes a callback added with [`add_periodic`](CommandManager::add_periodic),
+    /// returns false if it was already removed.
+    pub fn remove_periodic(&mut self, id: PeriodicId) -> bool {
+        let len = self.periodic_callbacks.len();
+        self.periodic_callbacks
+            .retain(|callback| callback.id != Some(id));
+        self.periodic_callbacks.len() != len
+    }
+
+    /// Sets the mode the robot is in, usually done by a [`RobotRunner`](crate::robot::RobotRunner).
+    ///
+    /// Switching to [`RobotMode::Disabled`] cancels every running command
+    /// that does not [`run_when_disabled`](CommandTrait::run_when_disabled).
+    pub fn set_mode(&mut self, mode: RobotMode) {
+        if self.mode == Some(mode) {
+            return;
+        }
+        self.mode = Some(mode);
+        if mode == RobotMode::Disabled {
+            for index in self.active_commands() {
+                if self
+                    .get_command_ref(index)
+                    .is_some_and(|command| !command.run_when_disabled())
+                {
+                    self.interrupt_state.insert(index, true);
+                }
+            }
+        }
+    }
+
+    /// Sets if panics in commands and periodic callbacks should be caught instead of unwinding through [`run`](CommandManager::run).
+    ///
+    /// A command that panics is interrupted, with `end(true)` called on a best-effort basis,
+    /// a periodic callback that panics, like a subsystem's `periodic`, is run again next cycle.
+    /// Panics are logged with the name of the command or callback, the panic hook still runs as usual.
+    pub fn set_catch_panics(&mut self, catch_panics: bool) {
+        self.catch_panics = catch_panics;
+    }
+
+    #[must_use]
+    pub const fn catch_panics(&self) -> bool {
+        self.catch_panics
+    }
+
+    /// Sets a callback that is given the name and error of every command that fails,
+    /// see [`CommandTrait::take_error`]. Errors are logged whether or not a handler is set.
+    pub fn set_error_handler(&mut self, handler: impl FnMut(&str, &CommandError) + 'static) {
+        self.error_handler = Some(Box::new(handler));
+    }
+
+    /// The error that ended the command last scheduled at `index`,
+    /// cleared when another command is scheduled there.
+    #[must_use]
+    pub fn command_error(&self, index: CommandIndex) -> Option<&CommandError> {
+        self.command_errors.get(&index)
+    }
+
+    /// The mode the robot is in, `None` until a mode is set in which case commands run as if enabled.
+    #[must_use]
+    pub const fn mode(&self) -> Option<RobotMode> {
+        self.mode
     }
 
     pub fn clear_conditional_schedulers(&mut self) {
-        self.cond_schedulers.clear();
+        let schedulers = std::mem::take(&mut self.cond_schedulers);
+        self.release_schedulers(schedulers);
+    }
+
+    /// Removes all bindings added to the given event loop,
+    /// their commands are interrupted if they are running.
+    pub fn clear_event_loop(&mut self, event_loop: EventLoop) {
+        if let Some(schedulers) = self.event_loops.remove(&event_loop) {
+            self.release_schedulers(schedulers);
+        }
+    }
+
+    /// Frees the preserved command slots of removed bindings so they can be reused.
+    fn release_schedulers(&mut self, schedulers: Vec<ConditionalScheduler>) {
+        for index in schedulers.iter().filter_map(ConditionalScheduler::index) {
+            let CommandIndex::PreservedCommand(idx) = index else {
+                continue;
+            };
+            if let Some(mut command) = self.preserved_commands[idx].take() {
+                if self.initialized_commands.contains(&index) {
+                    command.end(true);
+                }
+            }
+            self.remove_command(index);
+            self.interrupt_state.remove(&index);
+        }
+    }
+
+    /// Attaches an event loop to the command manager,
+    /// its bindings will be polled every [`run`](CommandManager::run) until it is detached.
+    pub fn attach_event_loop(&mut self, event_loop: EventLoop) {
+        self.attached_event_loops.insert(event_loop);
+    }
+
+    /// Detaches an event loop from the command manager,
+    /// its bindings will only be polled through [`poll_event_loop`](CommandManager::poll_event_loop).
+    pub fn detach_event_loop(&mut self, event_loop: EventLoop) {
+        self.attached_event_loops.remove(&event_loop);
+    }
+
+    /// Returns true if the given event loop is polled every [`run`](CommandManager::run).
+    #[must_use]
+    pub fn is_event_loop_attached(&self, event_loop: EventLoop) -> bool {
+        self.attached_event_loops.contains(&event_loop)
+    }
+}
+
+/// Introspection methods
+impl CommandManager {
+    /// Returns the name of a registered subsystem from its [`SubsystemSUID`].
+    #[must_use]
+    pub fn subsystem_name(&self, suid: SubsystemSUID) -> Option<&'static str> {
+        self.subsystems
+            .iter()
+            .find_map(|(id, name)| (*id == suid).then_some(*name))
+    }
+
+    /// Returns a plain data view of everything the command manager is currently tracking,
+    /// suitable for printing or sending to a dashboard.
+    #[must_use]
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let mut active = self.active_commands();
+        // the active commands come out of hash maps, sort them so snapshots are stable
+        active.sort_unstable();
+        let commands = active
+            .into_iter()
+            .filter_map(|index| {
+                let command = self.get_command_ref(index)?;
+                Some(CommandSnapshot {
+                    name: command.get_name(),
+                    index,
+                    requirements: command.get_requirements(),
+                    tree: command.tree(),
+                    schedule_id: self.schedule_ids.get(&index).copied().unwrap_or_default(),
+                    initialized: self.initialized_commands.contains(&index),
+                    time_running: self.init_times.get(&index).map_or(Duration::ZERO, |init| {
+                        now().saturating_duration_since(*init)
+                    }),
+                })
+            })
+            .collect();
+        let subsystems = self
+            .subsystems
+            .iter()
+            .map(|(suid, name)| SubsystemSnapshot {
+                name: (*name).to_owned(),
+                suid: *suid,
+                held_by: self
+                    .requirements
+                    .get(suid)
+                    .copied()
+                    .filter(|index| self.get_command_ref(*index).is_some()),
+                has_default_command: self
+                    .subsystem_to_default
+                    .get(suid)
+                    .is_some_and(|index| self.get_command_ref(*index).is_some()),
+            })
+            .collect();
+        let mut bindings = self
+            .cond_schedulers
+            .iter()
+            .chain(self.event_loops.values().flatten())
+            .filter_map(|scheduler| {
+                let index = scheduler.index()?;
+                Some(BindingSnapshot {
+                    command: self.get_command_ref(index)?.get_name(),
+                    index,
+                    event_loop: scheduler.event_loop(),
+                    polled: scheduler
+                        .event_loop()
+                        .is_none_or(|event_loop| self.is_event_loop_attached(event_loop)),
+                })
+            })
+            .collect::<Vec<_>>();
+        bindings.sort_unstable_by_key(|binding| binding.index);
+        SchedulerSnapshot {
+            commands,
+            subsystems,
+            bindings,
+        }
+    }
+}
+
+/// Scheduler event conditions
+impl CommandManager {
+    /// Returns a condition that is true for the cycle after a command with the given name finished without being interrupted.
+    ///
+    /// Commands are matched on the name they had when they were scheduled,
+    /// so commands whose name changes while running (like an [`FsmCommand`](crate::FsmCommand)) still match.
+    /// Bind it with [`Condition::on_true`] to chain behavior off of another command.
+    #[must_use]
+    pub fn command_finished(&self, name: impl Into<String>) -> Condition {
+        let name = name.into();
+        let events = self.events.clone();
+        Condition::new(move || events.finished.borrow().contains(&name))
+    }
+
+    /// Returns a condition that is true for the cycle after a command with the given name was interrupted.
+    ///
+    /// Commands are matched on the name they had when they were scheduled.
+    #[must_use]
+    pub fn command_interrupted(&self, name: impl Into<String>) -> Condition {
+        let name = name.into();
+        let events = self.events.clone();
+        Condition::new(move || events.interrupted.borrow().contains(&name))
+    }
+
+    /// Returns a condition that is true while the given subsystem is not required by any command
+    /// other than its default command.
+    ///
+    /// Bind it with [`Condition::on_true`] to react to the subsystem becoming idle.
+    #[must_use]
+    pub fn subsystem_idle(&self, subsystem: Requirement) -> Condition {
+        let suid = subsystem.suid();
+        let events = self.events.clone();
+        Condition::new(move || !events.busy_subsystems.borrow().contains(&suid))
+    }
+
+    /// Sets the time remaining in the current match period, usually fed from the driver station.
+    /// The remaining time counts down with the command manager clock until it is set again.
+    pub fn set_match_time(&mut self, remaining: Option<Duration>) {
+        self.events
+            .match_time
+            .set(remaining.map(|remaining| (now(), remaining)));
+    }
+
+    /// Returns the time remaining in the current match period, if it is known.
+    #[must_use]
+    pub fn match_time_remaining(&self) -> Option<Duration> {
+        self.events.match_time_remaining()
+    }
+
+    /// Returns a condition that is true while the match time is known and below `threshold`,
+    /// useful for binding end-game behavior.
+    #[must_use]
+    pub fn match_time_below(&self, threshold: Duration) -> Condition {
+        let events = self.events.clone();
+        Condition::new(move || {
+            events
+                .match_time_remaining()
+                .is_some_and(|remaining| remaining < threshold)
+        })
     }
 }
 
@@ -349,11 +889,38 @@ impl CommandManager {
     /// Will run all periodic callbacks, run all conditional schedulers, init all un-initialized commands, and run all commands
     /// in that order.
     pub fn run(&mut self) {
-        self.update();
-        self.run_subsystems();
-        self.run_cond_schedulers();
-        self.run_commands();
+        let cycle_start = Instant::now();
+        CYCLE_START.with(|start| start.set(Some(cycle_start)));
+        let _run_span = tracing::trace_span!("run").entered();
+        self.run_phase("update", tracing::trace_span!("update"), Self::update);
+        self.run_phase(
+            "run_subsystems",
+            tracing::trace_span!("run_subsystems"),
+            Self::run_subsystems,
+        );
+        self.run_phase(
+            "run_cond_schedulers",
+            tracing::trace_span!("run_cond_schedulers"),
+            Self::run_cond_schedulers,
+        );
+        self.run_phase(
+            "run_commands",
+            tracing::trace_span!("run_commands"),
+            Self::run_commands,
+        );
+        if wpilog::is_active() {
+            self.log_subsystem_owners();
+            wpilog::append_internal("/Scheduler/LoopTime", cycle_start.elapsed().as_secs_f64());
+        }
+        self.watchdog.finish_cycle(cycle_start.elapsed());
         tracing::trace!("Ran command scheduler");
+        CYCLE_START.with(|start| start.set(None));
+    }
+
+    fn run_phase(&mut self, name: &'static str, span: tracing::Span, phase: fn(&mut Self)) {
+        let start = Instant::now();
+        span.in_scope(|| phase(self));
+        self.watchdog.add_phase(name, start.elapsed());
     }
 
     fn update(&mut self) {
@@ -361,7 +928,7 @@ impl CommandManager {
             if let Some(queue) = &mut *queue.borrow_mut() {
                 queue.cmd_queue.drain(..).for_each(|command| {
                     let index = self.add_command(command);
-                    self.inner_schedule(index);
+                    self.schedule_unobserved(index, "Queued");
                 });
                 queue.cond_queue.drain(..).for_each(|scheduler| {
                     self.add_cond_scheduler(scheduler);
@@ -371,15 +938,7 @@ impl CommandManager {
     }
 
     fn run_subsystems(&mut self) {
-        for callback in &mut self.periodic_callbacks {
-            if let Some(last_run) = callback.1 {
-                let dt = last_run.elapsed();
-                callback.0(dt);
-            } else {
-                callback.0(Duration::from_secs(0));
-            }
-            callback.1 = Some(Instant::now());
-        }
+        self.run_periodics(true);
         for (suid, cmd_idx) in &self.subsystem_to_default {
             if !self.requirements.contains_key(suid) {
                 self.requirements.insert(*suid, *cmd_idx);
@@ -387,48 +946,270 @@ impl CommandManager {
         }
     }
 
-    fn run_cond_schedulers(&mut self) {
+    /// Runs every periodic callback that is due,
+    /// callbacks without a period are only run when `every_cycle` is true.
+    fn run_periodics(&mut self, every_cycle: bool) {
+        let catch_panics = self.catch_panics;
+        for callback in &mut self.periodic_callbacks {
+            let start = Instant::now();
+            // callbacks without a period of their own only run with the main loop
+            let every_cycle_only = callback.period.is_none_or(|period| period.is_zero());
+            if !callback.is_due(start) || (every_cycle_only && !every_cycle) {
+                continue;
+            }
+            // dt is measured between starts so time spent in the callback is included
+            let dt = callback
+                .last_run
+                .map_or(Duration::ZERO, |last_run| start - last_run);
+            let _span = tracing::trace_span!("periodic_callback", name = callback.name).entered();
+            if let Err(payload) = guard(catch_panics, || (callback.callback)(dt)) {
+                tracing::error!(
+                    "Periodic callback {} panicked: {}",
+                    callback.name,
+                    panic_message(payload.as_ref())
+                );
+            }
+            callback.last_run = Some(start);
+            if let Some(period) = callback.period {
+                // skip any runs that were missed instead of bursting to catch up
+                callback.next_run = if period.is_zero() {
+                    start
+                } else {
+                    let mut next_run = callback.next_run + period;
+                    while next_run <= start {
+                        next_run += period;
+                    }
+                    next_run
+                };
+            }
+            self.watchdog.add_epoch(
+                EpochKind::Periodic,
+                || callback.name.clone(),
+                start.elapsed(),
+            );
+        }
+    }
+
+    /// Runs the periodic callbacks with their own period that are due, used to run them between cycles.
+    pub(crate) fn run_due_periodics(&mut self) {
+        let _span = tracing::trace_span!("run_due_periodics").entered();
+        self.run_periodics(false);
+    }
+
+    /// The next time a periodic callback with its own period is due.
+    pub(crate) fn next_periodic_deadline(&self) -> Option<Instant> {
+        self.periodic_callbacks
+            .iter()
+            .filter(|callback| callback.period.is_some_and(|period| !period.is_zero()))
+            .map(|callback| callback.next_run)
+            .min()
+    }
+
+    /// Polls the bindings of the given event loop once, scheduling any commands whose conditions are met.
+    /// Commands scheduled this way will run on the next [`run`](CommandManager::run).
+    pub fn poll_event_loop(&mut self, event_loop: EventLoop) {
+        self.update();
         let to_schedule = self
+            .event_loops
+            .get_mut(&event_loop)
+            .map(|schedulers| {
+                schedulers
+                    .iter_mut()
+                    .filter_map(ConditionalScheduler::poll)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for index in to_schedule {
+            self.schedule_unobserved(index, "Bound");
+        }
+    }
+
+    fn run_cond_schedulers(&mut self) {
+        let mut to_schedule = self
             .cond_schedulers
             .iter_mut()
             .filter_map(ConditionalScheduler::poll)
             .collect::<Vec<_>>();
+        for event_loop in &self.attached_event_loops {
+            if let Some(schedulers) = self.event_loops.get_mut(event_loop) {
+                to_schedule.extend(schedulers.iter_mut().filter_map(ConditionalScheduler::poll));
+            }
+        }
         for index in to_schedule {
-            self.inner_schedule(index);
+            self.schedule_unobserved(index, "Bound");
         }
     }
 
     fn run_commands(&mut self) {
         let mut to_remove: Vec<CommandIndex> = Vec::new();
-        let mut cmds = self.requirements.values().collect::<Vec<&CommandIndex>>();
-        cmds.extend(self.orphaned_commands.iter());
+        let cmds = self.active_commands();
+        let catch_panics = self.catch_panics;
+        self.events.finished.borrow_mut().clear();
+        self.events.interrupted.borrow_mut().clear();
 
-        for index in cmds {
+        for index in &cmds {
             if let Some(command) = match index {
                 CommandIndex::Command(cmd) => &mut self.commands[*cmd],
                 CommandIndex::DefaultCommand(cmd) => &mut self.default_commands[*cmd],
                 CommandIndex::PreservedCommand(cmd) => &mut self.preserved_commands[*cmd],
             } {
+                let command_start = Instant::now();
+                let _command_span = tracing::trace_span!(
+                    "command",
+                    name = %command.get_name(),
+                    index = ?index,
+                    requirements = ?command.get_requirements(),
+                )
+                .entered();
                 if self.interrupt_state[index] {
-                    command.end(true);
+                    let ended = guard(catch_panics, || {
+                        tracing::trace_span!("end", interrupted = true)
+                            .in_scope(|| command.end(true));
+                    });
+                    if let Err(payload) = ended {
+                        report_panic(&command.get_name(), payload.as_ref());
+                    }
+                    if wpilog::is_active() {
+                        wpilog::append_internal(
+                            "/Scheduler/CommandInterrupted",
+                            command.get_name(),
+                        );
+                    }
+                    self.events.interrupted.borrow_mut().insert(scheduled_name(
+                        &self.scheduled_names,
+                        *index,
+                        command,
+                    ));
                     to_remove.push(*index);
+                    self.watchdog.add_epoch(
+                        EpochKind::Command,
+                        || command.get_name(),
+                        command_start.elapsed(),
+                    );
                     continue;
                 }
-                if !self.initialized_commands.contains(index) {
-                    command.init();
-                    self.initialized_commands.insert(*index);
-                }
-                //TODO: Add dt to periodic
-                command.periodic(Duration::from_secs(0));
-                if command.is_finished() {
-                    command.end(false);
+                let initialized_commands = &mut self.initialized_commands;
+                let init_times = &mut self.init_times;
+                let step = guard(catch_panics, || {
+                    if !initialized_commands.contains(index) {
+                        tracing::trace_span!("init").in_scope(|| command.init());
+                        if wpilog::is_active() {
+                            wpilog::append_internal(
+                                "/Scheduler/CommandInitialized",
+                                command.get_name(),
+                            );
+                        }
+                        initialized_commands.insert(*index);
+                        init_times.insert(*index, now());
+                        if let Some(error) = command.take_error() {
+                            return Err(error);
+                        }
+                    }
+                    //TODO: Add dt to periodic
+                    tracing::trace_span!("periodic")
+                        .in_scope(|| command.periodic(Duration::from_secs(0)));
+                    if let Some(error) = command.take_error() {
+                        return Err(error);
+                    }
+                    Ok(tracing::trace_span!("is_finished").in_scope(|| command.is_finished()))
+                });
+                let aborted = match step {
+                    Ok(Ok(true)) => {
+                        let ended = guard(catch_panics, || {
+                            tracing::trace_span!("end", interrupted = false)
+                                .in_scope(|| command.end(false));
+                        });
+                        if let Err(payload) = ended {
+                            report_panic(&command.get_name(), payload.as_ref());
+                        }
+                        if wpilog::is_active() {
+                            wpilog::append_internal(
+                                "/Scheduler/CommandFinished",
+                                command.get_name(),
+                            );
+                        }
+                        self.events.finished.borrow_mut().insert(scheduled_name(
+                            &self.scheduled_names,
+                            *index,
+                            command,
+                        ));
+                        to_remove.push(*index);
+                        false
+                    }
+                    Ok(Ok(false)) => false,
+                    Ok(Err(error)) => {
+                        let name = command.get_name();
+                        tracing::error!("Command {name} failed and was interrupted: {error}");
+                        if let Some(handler) = self.error_handler.as_mut() {
+                            handler(&name, &error);
+                        }
+                        self.command_errors.insert(*index, error);
+                        true
+                    }
+                    Err(payload) => {
+                        report_panic(&command.get_name(), payload.as_ref());
+                        true
+                    }
+                };
+                if aborted {
+                    // best effort, the command may have been left in a broken state
+                    let ended = guard(catch_panics, || {
+                        tracing::trace_span!("end", interrupted = true)
+                            .in_scope(|| command.end(true));
+                    });
+                    if let Err(payload) = ended {
+                        report_panic(&command.get_name(), payload.as_ref());
+                    }
+                    if wpilog::is_active() {
+                        wpilog::append_internal(
+                            "/Scheduler/CommandInterrupted",
+                            command.get_name(),
+                        );
+                    }
+                    self.events.interrupted.borrow_mut().insert(scheduled_name(
+                        &self.scheduled_names,
+                        *index,
+                        command,
+                    ));
                     to_remove.push(*index);
                 }
+                self.watchdog.add_epoch(
+                    EpochKind::Command,
+                    || command.get_name(),
+                    command_start.elapsed(),
+                );
             }
         }
         for index in to_remove {
             self.remove_command(index);
         }
+        let mut busy = self.events.busy_subsystems.borrow_mut();
+        busy.clear();
+        busy.extend(
+            self.requirements
+                .iter()
+                .filter(|(_, index)| !matches!(index, CommandIndex::DefaultCommand(_)))
+                .map(|(suid, _)| *suid),
+        );
+    }
+
+    fn log_subsystem_owners(&mut self) {
+        for (suid, name) in &self.subsystems {
+            let owner = self
+                .requirements
+                .get(suid)
+                .copied()
+                .filter(|index| self.get_command_ref(*index).is_some());
+            if self.logged_owners.get(suid) == Some(&owner) {
+                continue;
+            }
+            self.logged_owners.insert(*suid, owner);
+            let owner_name = owner
+                .and_then(|index| self.get_command_ref(index))
+                .map(CommandTrait::get_name)
+                .unwrap_or_default();
+            wpilog::append_internal(&format!("/Scheduler/Subsystems/{name}"), owner_name);
+        }
     }
 }
 
@@ -443,6 +1224,42 @@ impl CommandManager {
 
 
 
+/// Runs a callback, catching any panic if `catch_panics` is set.
+fn guard<T>(catch_panics: bool, callback: impl FnOnce() -> T) -> std::thread::Result<T> {
+    if catch_panics {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback))
+    } else {
+        Ok(callback())
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>")
+}
+
+/// The name a command had when it was scheduled, falling back to its current name.
+fn scheduled_name(
+    names: &FxHashMap<CommandIndex, String>,
+    index: CommandIndex,
+    command: &Command,
+) -> String {
+    names
+        .get(&index)
+        .cloned()
+        .unwrap_or_else(|| command.get_name())
+}
+
+fn report_panic(command: &str, payload: &(dyn std::any::Any + Send)) {
+    tracing::error!(
+        "Command {command} panicked and was interrupted: {}",
+        panic_message(payload)
+    );
+}
+
 impl Default for CommandManager {
     fn default() -> Self {
         Self::new()
@@ -451,6 +1268,7 @@ impl Default for CommandManager {
 impl Drop for CommandManager {
     fn drop(&mut self) {
         tracing::debug!("Dropping command manager");
+        wpilog::install(None);
         MANAGER_QUEUE.with(|queue| {
             *queue.borrow_mut() = None;
         });
@@ -0,0 +1,187 @@
+//! Publishing of the command manager's status in the same shape as WPILib's "Scheduler" sendable.
+//!
+//! Values are sent through an [`NtTransport`] so any NetworkTables 4 client can be plugged in,
+//! [`InProcessServer`] is an in-process stand-in for tests and simulation.
+//!
+//! # Examples
+//! ```
+//! use frclib_commands::{nt::{InProcessServer, NtValue, SchedulerPublisher}, CommandBuilder, CommandManager};
+//!
+//! let mut manager = CommandManager::new();
+//! let server = InProcessServer::new();
+//! let mut publisher = SchedulerPublisher::new(server.clone());
+//!
+//! CommandBuilder::new().build().with_name(&"spin").schedule();
+//! manager.run();
+//! publisher.update(&mut manager);
+//!
+//! assert_eq!(
+//!     server.get("/SmartDashboard/Scheduler/Names"),
+//!     Some(NtValue::StringArray(vec![String::from("spin")]))
+//! );
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use fxhash::FxHashMap;
+
+use crate::CommandManager;
+
+/// A value that can be sent over a NetworkTables topic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NtValue {
+    Boolean(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    BooleanArray(Vec<bool>),
+    IntArray(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    StringArray(Vec<String>),
+}
+impl NtValue {
+    /// The NetworkTables 4 type string of this value.
+    #[must_use]
+    pub const fn type_str(&self) -> &'static str {
+        match self {
+            Self::Boolean(_) => "boolean",
+            Self::Int(_) => "int",
+            Self::Double(_) => "double",
+            Self::String(_) => "string",
+            Self::BooleanArray(_) => "boolean[]",
+            Self::IntArray(_) => "int[]",
+            Self::DoubleArray(_) => "double[]",
+            Self::StringArray(_) => "string[]",
+        }
+    }
+}
+
+/// A connection to a NetworkTables 4 server or client.
+pub trait NtTransport {
+    /// Publishes a value to a topic, announcing the topic if needed.
+    fn publish(&mut self, topic: &str, value: NtValue);
+
+    /// Returns the latest value a remote client wrote to the topic since the last call, if any.
+    fn take_remote(&mut self, topic: &str) -> Option<NtValue>;
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    published: FxHashMap<String, NtValue>,
+    remote: FxHashMap<String, NtValue>,
+}
+
+/// An in-process [`NtTransport`] that stands in for a NetworkTables server.
+///
+/// Clones share the same state, keep one clone to act as the dashboard.
+#[derive(Debug, Clone, Default)]
+pub struct InProcessServer {
+    state: Rc<RefCell<ServerState>>,
+}
+impl InProcessServer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the latest value published to a topic.
+    #[must_use]
+    pub fn get(&self, topic: &str) -> Option<NtValue> {
+        self.state.borrow().published.get(topic).cloned()
+    }
+
+    /// Writes a value to a topic as a remote client would.
+    pub fn set_remote(&self, topic: &str, value: NtValue) {
+        self.state
+            .borrow_mut()
+            .remote
+            .insert(topic.to_owned(), value);
+    }
+}
+impl NtTransport for InProcessServer {
+    fn publish(&mut self, topic: &str, value: NtValue) {
+        self.state
+            .borrow_mut()
+            .published
+            .insert(topic.to_owned(), value);
+    }
+
+    fn take_remote(&mut self, topic: &str) -> Option<NtValue> {
+        self.state.borrow_mut().remote.remove(topic)
+    }
+}
+
+/// Publishes the running commands of a [`CommandManager`] and applies cancel requests from dashboards.
+///
+/// Under the table (`/SmartDashboard/Scheduler` by default) this publishes
+/// `.type`, `Names`, `Ids` and `Cancel`, matching WPILib's scheduler sendable.
+/// Ids written to `Cancel` by a dashboard are cancelled on the next [`update`](SchedulerPublisher::update).
+///
+/// Published ids are the [`schedule_id`](crate::snapshot::CommandSnapshot::schedule_id) of each command,
+/// so a stale cancel request never reaches a command that was scheduled later.
+#[derive(Debug)]
+pub struct SchedulerPublisher<T: NtTransport> {
+    transport: T,
+    table: String,
+}
+impl<T: NtTransport> SchedulerPublisher<T> {
+    pub fn new(transport: T) -> Self {
+        Self::with_table(transport, "/SmartDashboard/Scheduler")
+    }
+
+    pub fn with_table(transport: T, table: impl Into<String>) -> Self {
+        Self {
+            transport,
+            table: table.into(),
+        }
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{name}", self.table)
+    }
+
+    /// Applies any pending cancel requests then publishes the current scheduler status,
+    /// meant to be called once per cycle after [`CommandManager::run`].
+    pub fn update(&mut self, manager: &mut CommandManager) {
+        let cancel_topic = self.topic("Cancel");
+        if let Some(NtValue::IntArray(ids)) = self.transport.take_remote(&cancel_topic) {
+            let commands = manager.snapshot().commands;
+            for id in ids {
+                let Some(command) = commands
+                    .iter()
+                    .find(|command| i64::try_from(command.schedule_id) == Ok(id))
+                else {
+                    continue;
+                };
+                if manager.cancel(command.index) {
+                    tracing::debug!("Cancelled {} from the dashboard", command.name);
+                }
+            }
+        }
+
+        let snapshot = manager.snapshot();
+        let (names, ids) = snapshot
+            .commands
+            .into_iter()
+            .map(|command| {
+                let id = i64::try_from(command.schedule_id).unwrap_or(i64::MAX);
+                (command.name, id)
+            })
+            .unzip();
+        let type_topic = self.topic(".type");
+        let names_topic = self.topic("Names");
+        let ids_topic = self.topic("Ids");
+        self.transport
+            .publish(&type_topic, NtValue::String(String::from("Scheduler")));
+        self.transport
+            .publish(&names_topic, NtValue::StringArray(names));
+        self.transport.publish(&ids_topic, NtValue::IntArray(ids));
+        self.transport
+            .publish(&cancel_topic, NtValue::IntArray(Vec::new()));
+    }
+
+    /// Returns the transport this publisher sends through.
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
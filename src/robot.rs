@@ -0,0 +1,229 @@
+//! A fixed-rate robot loop around a [`CommandManager`], similar to WPILib's `TimedRobot`.
+//!
+//! # Examples
+//! ```no_run
+//! use frclib_commands::{robot::{Robot, RobotMode, RobotRunner}, CommandManager};
+//! use std::time::Duration;
+//!
+//! struct MyRobot;
+//! impl Robot for MyRobot {
+//!     fn mode_init(&mut self, mode: RobotMode, _manager: &mut CommandManager) {
+//!         println!("Entering {mode:?}");
+//!     }
+//! }
+//!
+//! let mut runner = RobotRunner::new(CommandManager::new(), MyRobot)
+//!     .mode_source(|| RobotMode::Teleop);
+//! runner.add_periodic("odometry", |dt| println!("Odometry: {dt:?}"), Duration::from_millis(5), Duration::ZERO);
+//! runner.run();
+//! ```
+
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use fxhash::FxHashMap;
+
+use crate::{conditions::EventLoop, manager::PeriodicId, CommandManager};
+
+/// The default period of the main robot loop.
+pub const DEFAULT_PERIOD: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RobotMode {
+    #[default]
+    Disabled,
+    Autonomous,
+    Teleop,
+    Test,
+}
+
+/// Hooks called by a [`RobotRunner`], all of them default to doing nothing.
+pub trait Robot {
+    /// Called once before the first cycle.
+    fn robot_init(&mut self, _manager: &mut CommandManager) {}
+
+    /// Called every cycle regardless of mode, after the mode's periodic hook.
+    fn robot_periodic(&mut self, _manager: &mut CommandManager) {}
+
+    /// Called on the first cycle of a mode.
+    fn mode_init(&mut self, _mode: RobotMode, _manager: &mut CommandManager) {}
+
+    /// Called every cycle while in a mode, including the first.
+    fn mode_periodic(&mut self, _mode: RobotMode, _manager: &mut CommandManager) {}
+
+    /// Called when leaving a mode, before the next mode's init.
+    fn mode_exit(&mut self, _mode: RobotMode, _manager: &mut CommandManager) {}
+}
+
+/// Owns a [`CommandManager`] and runs it at a fixed period.
+///
+/// Cycles are scheduled against absolute deadlines so time spent running the cycle
+/// does not make the loop drift, missed cycles are skipped rather than run back to back.
+pub struct RobotRunner<R: Robot> {
+    manager: CommandManager,
+    robot: R,
+    period: Duration,
+    mode_source: Box<dyn FnMut() -> RobotMode>,
+    mode: Option<RobotMode>,
+    mode_event_loops: FxHashMap<RobotMode, Vec<EventLoop>>,
+    next_cycle: Option<Instant>,
+}
+impl<R: Robot + Debug> Debug for RobotRunner<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RobotRunner")
+            .field("robot", &self.robot)
+            .field("period", &self.period)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+impl<R: Robot> RobotRunner<R> {
+    /// Creates a runner with the [`DEFAULT_PERIOD`] that stays disabled until given a mode source.
+    pub fn new(manager: CommandManager, robot: R) -> Self {
+        Self {
+            manager,
+            robot,
+            period: DEFAULT_PERIOD,
+            mode_source: Box::new(|| RobotMode::Disabled),
+            mode: None,
+            mode_event_loops: FxHashMap::default(),
+            next_cycle: None,
+        }
+    }
+
+    /// Sets the period of the main loop.
+    ///
+    /// # Panics
+    /// If `period` is zero.
+    #[must_use]
+    pub const fn with_period(mut self, period: Duration) -> Self {
+        assert!(!period.is_zero(), "The robot loop period must not be zero");
+        self.period = period;
+        self
+    }
+
+    /// Sets where the current mode is read from every cycle, usually the driver station.
+    #[must_use]
+    pub fn mode_source(mut self, mode_source: impl FnMut() -> RobotMode + 'static) -> Self {
+        self.mode_source = Box::new(mode_source);
+        self
+    }
+
+    /// Attaches the event loop to the command manager while the robot is in the given mode.
+    pub fn bind_event_loop(&mut self, mode: RobotMode, event_loop: EventLoop) {
+        self.mode_event_loops
+            .entry(mode)
+            .or_default()
+            .push(event_loop);
+        if self.mode == Some(mode) {
+            self.manager.attach_event_loop(event_loop);
+        }
+    }
+
+    /// Adds a callback that is run every `period`, starting `offset` from now,
+    /// independent of the main loop's period.
+    /// The callback is given the time since it last ran,
+    /// a zero `period` runs it once every cycle of the main loop.
+    ///
+    /// Remove it with [`CommandManager::remove_periodic`].
+    pub fn add_periodic(
+        &mut self,
+        name: impl Into<String>,
+        callback: impl FnMut(Duration) + 'static,
+        period: Duration,
+        offset: Duration,
+    ) -> PeriodicId {
+        self.manager
+            .add_named_periodic(name, callback, period, offset)
+    }
+
+    pub fn manager(&mut self) -> &mut CommandManager {
+        &mut self.manager
+    }
+
+    pub fn robot(&mut self) -> &mut R {
+        &mut self.robot
+    }
+
+    #[must_use]
+    pub const fn mode(&self) -> Option<RobotMode> {
+        self.mode
+    }
+
+    /// Runs a single cycle of the main loop immediately: mode transitions, hooks and the command manager.
+    pub fn step(&mut self) {
+        let mode = (self.mode_source)();
+        match self.mode {
+            None => {
+                self.robot.robot_init(&mut self.manager);
+                self.enter_mode(mode);
+            }
+            Some(current) if current != mode => {
+                tracing::debug!("Switching robot mode from {current:?} to {mode:?}");
+                self.robot.mode_exit(current, &mut self.manager);
+                for event_loop in self.mode_event_loops.get(&current).into_iter().flatten() {
+                    self.manager.detach_event_loop(*event_loop);
+                }
+                self.enter_mode(mode);
+            }
+            Some(_) => {}
+        }
+        self.robot.mode_periodic(mode, &mut self.manager);
+        self.robot.robot_periodic(&mut self.manager);
+        self.manager.run();
+    }
+
+    fn enter_mode(&mut self, mode: RobotMode) {
+        self.mode = Some(mode);
+        self.manager.set_mode(mode);
+        for event_loop in self.mode_event_loops.get(&mode).into_iter().flatten() {
+            self.manager.attach_event_loop(*event_loop);
+        }
+        self.robot.mode_init(mode, &mut self.manager);
+    }
+
+    /// Runs the main loop and periodic callbacks until `deadline`, or forever if `None`.
+    fn run_until(&mut self, deadline: Option<Instant>) {
+        loop {
+            let next_cycle = *self.next_cycle.get_or_insert_with(Instant::now);
+            let next = self
+                .manager
+                .next_periodic_deadline()
+                .map_or(next_cycle, |periodic| periodic.min(next_cycle));
+            if deadline.is_some_and(|deadline| next > deadline) {
+                if let Some(deadline) = deadline {
+                    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                }
+                return;
+            }
+            std::thread::sleep(next.saturating_duration_since(Instant::now()));
+
+            let now = Instant::now();
+            if now >= next_cycle {
+                self.step();
+                let mut next_cycle = next_cycle + self.period;
+                let now = Instant::now();
+                if next_cycle <= now {
+                    tracing::warn!("Robot loop overran its {:?} period", self.period);
+                    while next_cycle <= now {
+                        next_cycle += self.period;
+                    }
+                }
+                self.next_cycle = Some(next_cycle);
+            }
+            self.manager.run_due_periodics();
+        }
+    }
+
+    /// Runs the robot for the given duration, mostly useful for simulation and tests.
+    pub fn run_for(&mut self, duration: Duration) {
+        self.run_until(Some(Instant::now() + duration));
+    }
+
+    /// Runs the robot forever.
+    pub fn run(&mut self) {
+        self.run_until(None);
+    }
+}
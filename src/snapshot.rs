@@ -0,0 +1,135 @@
+//! Plain data views of the command manager's state,
+//! see [`CommandManager::snapshot`](crate::CommandManager::snapshot),
+//! and of the structure of composed commands, see [`Command::tree`](crate::Command::tree).
+//!
+//! With the `serde` feature enabled all of these types are serializable.
+
+use std::{fmt::Display, time::Duration};
+
+use crate::{conditions::EventLoop, CommandIndex, SubsystemSUID};
+
+/// The state of the command manager at a single point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchedulerSnapshot {
+    /// Every command that is running or waiting to be initialized or ended, sorted by index.
+    pub commands: Vec<CommandSnapshot>,
+    /// Every registered subsystem.
+    pub subsystems: Vec<SubsystemSnapshot>,
+    /// Every registered conditional binding, sorted by index.
+    pub bindings: Vec<BindingSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandSnapshot {
+    pub name: String,
+    pub index: CommandIndex,
+    /// Unique to this run of the command, indices are reused by later commands.
+    pub schedule_id: u64,
+    pub requirements: Vec<SubsystemSUID>,
+    /// If the command has had its `init` called.
+    pub initialized: bool,
+    /// Time since the command was initialized, zero if it has not been yet.
+    pub time_running: Duration,
+    /// The structure of the command if it is composed of other commands.
+    pub tree: CommandNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubsystemSnapshot {
+    pub name: String,
+    pub suid: SubsystemSUID,
+    /// The command currently holding this subsystem, if any.
+    pub held_by: Option<CommandIndex>,
+    pub has_default_command: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BindingSnapshot {
+    /// The name of the command the binding schedules.
+    pub command: String,
+    pub index: CommandIndex,
+    /// The event loop the binding belongs to, `None` for bindings polled every cycle.
+    pub event_loop: Option<EventLoop>,
+    /// If the binding is polled every cycle, either from being in no event loop or an attached one.
+    pub polled: bool,
+}
+
+/// The type of a node in a [`CommandNode`] tree, mirrors the variants of [`Command`](crate::Command).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommandKind {
+    Parallel(ParallelKind),
+    Sequential,
+    Simple,
+    Const,
+    Custom,
+    Named,
+    Wait,
+    Proxy,
+    ExtraRequirements,
+}
+
+/// When a parallel group finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParallelKind {
+    /// Once all of its members have finished, see [`Command::parallel`](crate::Command::parallel).
+    All,
+    /// Once any of its members finishes, see [`Command::race`](crate::Command::race).
+    Race,
+    /// Once its first member finishes, see [`Command::deadline`](crate::Command::deadline).
+    Deadline,
+}
+
+/// A node in the tree of a composed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandNode {
+    /// The name from [`get_name`](crate::CommandTrait::get_name).
+    pub name: String,
+    pub kind: CommandKind,
+    pub requirements: Vec<SubsystemSUID>,
+    pub children: Vec<CommandNode>,
+}
+
+impl Display for SchedulerSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Commands:")?;
+        for command in &self.commands {
+            writeln!(
+                f,
+                "  {:?} {} requires {:?}, {}",
+                command.index,
+                command.name,
+                command.requirements,
+                if command.initialized {
+                    format!("running for {:?}", command.time_running)
+                } else {
+                    String::from("not initialized")
+                }
+            )?;
+        }
+        writeln!(f, "Subsystems:")?;
+        for subsystem in &self.subsystems {
+            match subsystem.held_by {
+                Some(index) => writeln!(f, "  {} held by {:?}", subsystem.name, index)?,
+                None => writeln!(f, "  {} idle", subsystem.name)?,
+            }
+        }
+        writeln!(f, "Bindings:")?;
+        for binding in &self.bindings {
+            writeln!(
+                f,
+                "  {:?} {}{}",
+                binding.index,
+                binding.command,
+                if binding.polled { "" } else { " (not polled)" }
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,17 @@
 
 use std::{cell::RefCell, sync::atomic::{AtomicBool, Ordering}, rc::Rc};
 
-use crate::conditions::Condition;
+use crate::{clone_mv, conditions::Condition};
 
 
 
 #[allow(dead_code, clippy::collection_is_never_read)]
 #[test]
 fn test_manager() {
-    use crate::clone_mv;
     use super::*;
     use std::time::Duration;
     thread_local! {
-        static TEST_MARKERS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        static TEST_MARKERS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
     }
 
     fn add_marker(marker: &str) {
@@ -76,7 +75,7 @@ fn test_manager() {
         end: false,
     }));
 
-    manager.schedule(command);
+    assert!(manager.schedule(command).is_scheduled());
 
     let variable: Vec<f64> = Vec::new();
     let variable2: Option<u8> = None;
@@ -146,7 +145,7 @@ fn test_manager() {
 
     assert_marker!("custom_command_is_finished");
     assert_marker!("subsystem_periodic");
-    // assert_marker!("subsystem_log"); TODO
+    assert_marker!("subsystem_log");
     assert_marker!("cmd_init");
     assert_marker!("cmd_periodic");
     assert_marker!("cmd_end");
@@ -154,3 +153,1863 @@ fn test_manager() {
     assert_marker!("cond_eval");
     assert_marker!("cond_sched_init");
 }
+
+#[test]
+fn test_command_lifecycle() {
+    use super::*;
+    use std::cell::Cell;
+
+    struct ArmSubsystem;
+    impl Subsystem for ArmSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+    struct WristSubsystem;
+    impl Subsystem for WristSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let arm = SubsystemCell::<ArmSubsystem>::generate(&mut manager);
+    let wrist = SubsystemCell::<WristSubsystem>::generate(&mut manager);
+    let counting = |periodics: &Rc<Cell<u32>>, ends: &Rc<Cell<u32>>| {
+        CommandBuilder::new()
+            .periodic(clone_mv!(
+                periodics >> |_period| periodics.set(periodics.get() + 1)
+            ))
+            .end(clone_mv!(
+                ends >> |interrupted| {
+                    assert!(interrupted);
+                    ends.set(ends.get() + 1);
+                }
+            ))
+    };
+
+    // a command holding several requirements still runs and ends once per cycle
+    let (stow_periodics, stow_ends) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    counting(&stow_periodics, &stow_ends)
+        .with_subsystems(&[&arm, &wrist])
+        .build()
+        .schedule();
+    manager.run();
+    assert_eq!(stow_periodics.get(), 1);
+
+    // a displaced command is ended even though it no longer holds any requirement
+    let (aim_periodics, aim_ends) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    counting(&aim_periodics, &aim_ends)
+        .with_subsystems(&[&arm, &wrist])
+        .build()
+        .schedule();
+    manager.run();
+    assert_eq!(stow_ends.get(), 1);
+    assert_eq!(stow_periodics.get(), 1);
+    assert_eq!(aim_periodics.get(), 1);
+
+    // removing the displaced command left the new command's requirements alone
+    let (hold_periodics, hold_ends) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    counting(&hold_periodics, &hold_ends)
+        .with_subsystem(&wrist)
+        .build()
+        .schedule();
+    manager.run();
+    manager.run();
+    assert_eq!(aim_ends.get(), 1);
+    assert_eq!(stow_ends.get(), 1);
+    assert_eq!(hold_periodics.get(), 2);
+
+    // default commands are kept after being interrupted and resume once their subsystem is free
+    thread_local! {
+        static DEFAULT_INITS: Cell<u32> = const { Cell::new(0) };
+    }
+    struct ClimberSubsystem;
+    impl Subsystem for ClimberSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+
+        fn default_command(&mut self) -> Option<Command> {
+            Some(
+                CommandBuilder::new()
+                    .init(|| DEFAULT_INITS.with(|inits| inits.set(inits.get() + 1)))
+                    .build(),
+            )
+        }
+    }
+    let climber = SubsystemCell::<ClimberSubsystem>::generate(&mut manager);
+    manager.run();
+    manager.run();
+    assert_eq!(DEFAULT_INITS.with(Cell::get), 1);
+    CommandBuilder::new()
+        .is_finished(|| true)
+        .with_subsystem(&climber)
+        .build()
+        .schedule();
+    manager.run();
+    manager.run();
+    manager.run();
+    assert_eq!(DEFAULT_INITS.with(Cell::get), 2);
+}
+
+#[test]
+fn test_event_loop() {
+    use super::*;
+    use crate::conditions::EventLoop;
+    use std::cell::Cell;
+
+    let mut manager = CommandManager::new();
+    let event_loop = EventLoop::new();
+
+    let inits = Rc::new(Cell::new(0));
+    let cond = Condition::new(|| true).with_event_loop(event_loop);
+    cond.on_true(
+        CommandBuilder::new()
+            .init(clone_mv!(inits >> || inits.set(inits.get() + 1)))
+            .is_finished(|| true)
+            .build(),
+    );
+
+    // bindings on an event loop are not polled by `run`
+    manager.run();
+    manager.run();
+    assert_eq!(inits.get(), 0);
+
+    manager.poll_event_loop(event_loop);
+    manager.run();
+    assert_eq!(inits.get(), 1);
+
+    // rising edge was already consumed
+    manager.attach_event_loop(event_loop);
+    manager.run();
+    assert_eq!(inits.get(), 1);
+    assert!(manager.is_event_loop_attached(event_loop));
+
+    // clearing the loop interrupts its running commands and frees their slots
+    let ends = Rc::new(Cell::new(0));
+    Condition::new(|| true).with_event_loop(event_loop).on_true(
+        CommandBuilder::new()
+            .end(clone_mv!(ends >> |_interrupted| ends.set(ends.get() + 1)))
+            .build(),
+    );
+    manager.run();
+    manager.run();
+    assert_eq!(manager.snapshot().commands.len(), 1);
+    manager.clear_event_loop(event_loop);
+    assert_eq!(ends.get(), 1);
+    assert!(manager.snapshot().commands.is_empty());
+    manager.run();
+    assert_eq!(ends.get(), 1);
+
+    // the freed slots are reused by the next binding
+    Condition::new(|| false)
+        .with_event_loop(event_loop)
+        .on_true(Command::empty());
+    manager.run();
+    let bindings = manager.snapshot().bindings;
+    assert_eq!(bindings.len(), 1);
+    assert_eq!(bindings[0].index, CommandIndex::PreservedCommand(0));
+}
+
+#[test]
+fn test_hid_conditions() {
+    use crate::conditions::BooleanSupplier;
+    use crate::hid::{Controller, ControllerState, InputSource, SimInput, XboxController};
+
+    let input = SimInput::new();
+    let controller = XboxController::new(input.clone());
+
+    let a = controller.a();
+    let trigger = controller.left_trigger(0.5);
+    let pov_up = controller.pov_up();
+    assert!(!a.get_as_boolean());
+    assert!(!trigger.get_as_boolean());
+    assert!(!pov_up.get_as_boolean());
+
+    input.set_button(1, true);
+    input.set_axis(XboxController::LEFT_TRIGGER, 0.75);
+    input.set_pov(Some(0));
+    assert!(a.get_as_boolean());
+    assert!(trigger.get_as_boolean());
+    assert!(pov_up.get_as_boolean());
+    assert!(!controller.b().get_as_boolean());
+
+    input.set_button(1, false);
+    assert!(!a.get_as_boolean());
+
+    // conditions read single inputs instead of copying the whole state
+    struct ButtonsOnly;
+    impl InputSource for ButtonsOnly {
+        fn state(&self) -> ControllerState {
+            panic!("conditions should not read the whole state");
+        }
+        fn button(&self, button: u8) -> bool {
+            button == 3
+        }
+    }
+    let controller = Controller::new(ButtonsOnly);
+    assert!(controller.button(3).get_as_boolean());
+    assert!(!controller.button(1).get_as_boolean());
+}
+
+#[test]
+fn test_multi_press_and_chord() {
+    use crate::conditions::BooleanSupplier;
+    use std::{cell::Cell, time::Duration};
+
+    let button = Rc::new(Cell::new(false));
+    let double_tap = Condition::new(clone_mv!(button >> || button.get()))
+        .multi_press(2, Duration::from_secs(10));
+
+    let mut presses = Vec::new();
+    for pressed in [true, false, true, true, false, true] {
+        button.set(pressed);
+        presses.push(double_tap.get_as_boolean());
+    }
+    assert_eq!(presses, [false, false, true, false, false, false]);
+
+    // a count of 0 behaves like 1
+    button.set(false);
+    let tap = Condition::new(clone_mv!(button >> || button.get()))
+        .multi_press(0, Duration::from_secs(10));
+    let mut presses = Vec::new();
+    for pressed in [false, true, true, false, true] {
+        button.set(pressed);
+        presses.push(tap.get_as_boolean());
+    }
+    assert_eq!(presses, [false, true, false, false, true]);
+
+    let left = Rc::new(Cell::new(false));
+    let right = Rc::new(Cell::new(false));
+    let chord = Condition::chord(
+        &[
+            Condition::new(clone_mv!(left >> || left.get())),
+            Condition::new(clone_mv!(right >> || right.get())),
+        ],
+        Duration::from_secs(10),
+    );
+    left.set(true);
+    assert!(!chord.get_as_boolean());
+    right.set(true);
+    assert!(chord.get_as_boolean());
+    left.set(false);
+    assert!(!chord.get_as_boolean());
+
+    // a press later than the window after the first one is rejected
+    let chord = Condition::chord(
+        &[
+            Condition::new(clone_mv!(left >> || left.get())),
+            Condition::new(clone_mv!(right >> || right.get())),
+        ],
+        Duration::from_millis(5),
+    );
+    right.set(false);
+    left.set(true);
+    assert!(!chord.get_as_boolean());
+    std::thread::sleep(Duration::from_millis(20));
+    right.set(true);
+    assert!(!chord.get_as_boolean());
+
+    // every input is polled once per cycle, so single-poll conditions can be chorded
+    let shift = Rc::new(Cell::new(true));
+    button.set(false);
+    let chord = Condition::chord(
+        &[
+            Condition::new(clone_mv!(shift >> || shift.get())),
+            Condition::new(clone_mv!(button >> || button.get()))
+                .multi_press(2, Duration::from_secs(10)),
+        ],
+        Duration::from_secs(10),
+    );
+    let mut fired = Vec::new();
+    for pressed in [true, false, true, true] {
+        button.set(pressed);
+        fired.push(chord.get_as_boolean());
+    }
+    assert_eq!(fired, [false, false, true, false]);
+}
+
+#[test]
+fn test_cross_thread_conditions() {
+    use crate::conditions::BooleanSupplier;
+    use std::sync::{mpsc, Arc};
+
+    let flag = Arc::new(AtomicBool::new(false));
+    let from_flag = Condition::from_atomic_bool(flag.clone());
+    std::thread::spawn(move || flag.store(true, Ordering::Release))
+        .join()
+        .expect("flag thread panicked");
+    assert!(from_flag.get_as_boolean());
+
+    let (sender, receiver) = mpsc::channel();
+    let from_channel = Condition::from_receiver(receiver);
+    assert!(!from_channel.get_as_boolean());
+    std::thread::spawn(move || {
+        sender.send(false).expect("receiver dropped");
+        sender.send(true).expect("receiver dropped");
+    })
+    .join()
+    .expect("sender thread panicked");
+    assert!(from_channel.get_as_boolean());
+    // the sender is gone, the last value is latched
+    assert!(from_channel.get_as_boolean());
+}
+
+#[test]
+fn test_scheduler_event_conditions() {
+    use super::*;
+    use crate::conditions::BooleanSupplier;
+    use std::{cell::Cell, time::Duration};
+
+    struct EventSubsystem;
+    impl Subsystem for EventSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let subsystem = SubsystemCell::<EventSubsystem>::generate(&mut manager);
+
+    let chained = Rc::new(Cell::new(0));
+    manager.command_finished("first").on_true(
+        CommandBuilder::new()
+            .init(clone_mv!(chained >> || chained.set(chained.get() + 1)))
+            .is_finished(|| true)
+            .build(),
+    );
+    let idle = manager.subsystem_idle(&subsystem);
+
+    let finish = Rc::new(Cell::new(false));
+    CommandBuilder::new()
+        .is_finished(clone_mv!(finish >> || finish.get()))
+        .with_subsystem(&subsystem)
+        .build()
+        .with_name(&"first")
+        .schedule();
+
+    manager.run();
+    assert!(!idle.get_as_boolean());
+    finish.set(true);
+    manager.run();
+    assert!(idle.get_as_boolean());
+    assert_eq!(chained.get(), 0);
+    manager.run();
+    manager.run();
+    assert_eq!(chained.get(), 1);
+
+    // commands are matched on the name they were scheduled with
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Step {
+        Start,
+        Done,
+    }
+    let renamed = manager.command_finished("steps[Start]");
+    FsmCommand::new(Step::Start)
+        .with_name(&"steps")
+        .state(
+            Step::Start,
+            CommandBuilder::new().is_finished(|| true).build(),
+        )
+        .transition_on_finish(Step::Start, Step::Done)
+        .final_state(Step::Done)
+        .schedule();
+    let mut seen = false;
+    for _ in 0..4 {
+        manager.run();
+        seen |= renamed.get_as_boolean();
+    }
+    assert!(seen);
+
+    let end_game = manager.match_time_below(Duration::from_secs(20));
+    assert!(!end_game.get_as_boolean());
+    manager.set_match_time(Some(Duration::from_secs(30)));
+    assert!(!end_game.get_as_boolean());
+    manager.set_match_time(Some(Duration::from_secs(15)));
+    assert!(end_game.get_as_boolean());
+}
+
+#[test]
+fn test_snapshot() {
+    use super::*;
+
+    struct SnapshotSubsystem;
+    impl Subsystem for SnapshotSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+        fn name(&self) -> &'static str {
+            "SnapshotSubsystem"
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let subsystem = SubsystemCell::<SnapshotSubsystem>::generate(&mut manager);
+
+    CommandBuilder::new()
+        .with_subsystem(&subsystem)
+        .build()
+        .with_name(&"holder")
+        .schedule();
+    Condition::new(|| false).on_true(Command::empty().with_name(&"bound"));
+    manager.run();
+
+    let snapshot = manager.snapshot();
+    assert_eq!(snapshot.commands.len(), 1);
+    assert_eq!(snapshot.commands[0].name, "holder");
+    assert!(snapshot.commands[0].initialized);
+    assert_eq!(snapshot.commands[0].requirements, vec![subsystem.suid()]);
+    assert_eq!(snapshot.subsystems.len(), 1);
+    assert_eq!(snapshot.subsystems[0].name, "SnapshotSubsystem");
+    assert_eq!(
+        snapshot.subsystems[0].held_by,
+        Some(snapshot.commands[0].index)
+    );
+    assert!(!snapshot.subsystems[0].has_default_command);
+    assert_eq!(snapshot.bindings.len(), 1);
+    assert_eq!(snapshot.bindings[0].command, "bound");
+    assert!(snapshot.bindings[0].polled);
+    assert!(snapshot.to_string().contains("SnapshotSubsystem held by"));
+
+    // commands are listed in index order no matter how they are stored
+    for name in ["a", "b", "c", "d"] {
+        Command::empty().with_name(&name).schedule();
+    }
+    manager.run();
+    let names = manager
+        .snapshot()
+        .commands
+        .into_iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["holder", "a", "b", "c", "d"]);
+}
+
+#[test]
+fn test_command_tree() {
+    use super::*;
+    use crate::snapshot::{CommandKind, ParallelKind};
+    use std::time::Duration;
+
+    let tree = Command::empty()
+        .with_name(&"first")
+        .race_with(Command::wait_for(Duration::from_secs(1)))
+        .before(Command::empty())
+        .tree();
+    assert_eq!(tree.kind, CommandKind::Sequential);
+    assert_eq!(tree.children.len(), 2);
+    assert_eq!(
+        tree.children[0].kind,
+        CommandKind::Parallel(ParallelKind::Race)
+    );
+    assert_eq!(tree.children[0].children[0].name, "first");
+    assert_eq!(
+        tree.children[0].children[0].children[0].kind,
+        CommandKind::Simple
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&tree).expect("command tree should serialize");
+        assert_eq!(json["kind"], "Sequential");
+        assert_eq!(json["children"][0]["kind"]["Parallel"], "Race");
+        assert_eq!(json["children"][0]["children"][0]["name"], "first");
+    }
+}
+
+#[test]
+fn test_command_diagrams() {
+    use super::*;
+    use std::time::Duration;
+
+    struct Drive;
+    impl Subsystem for Drive {
+        fn construct() -> Self {
+            Self
+        }
+        fn name(&self) -> &'static str {
+            "Drive"
+        }
+    }
+    struct Shooter;
+    impl Subsystem for Shooter {
+        fn construct() -> Self {
+            Self
+        }
+        fn name(&self) -> &'static str {
+            "Shooter"
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let drive = SubsystemCell::<Drive>::generate(&mut manager);
+    let shooter = SubsystemCell::<Shooter>::generate(&mut manager);
+    let tree = CommandBuilder::new()
+        .with_subsystem(&drive)
+        .build()
+        .with_name(&"drive")
+        .race_with(Command::wait_for(Duration::from_secs(1)))
+        .before(
+            CommandBuilder::new()
+                .with_subsystem(&shooter)
+                .build()
+                .with_name(&"shoot \"fast\""),
+        )
+        .tree();
+    let namer = |suid| manager.subsystem_name(suid).unwrap_or_default().to_owned();
+
+    assert_eq!(
+        tree.to_dot_with(&namer),
+        r#"digraph command {
+    node [fontname="sans-serif"];
+    n0 [label="sequence\nrequires: Drive, Shooter", shape=box];
+    n1 [label="race\nrequires: Drive", shape=box];
+    n2 [label="drive\nrequires: Drive", shape=ellipse];
+    n3 [label="Unnamed Command\nrequires: Drive", shape=ellipse];
+    n2 -> n3;
+    n1 -> n2;
+    n4 [label="TimedCommand(1s)", shape=ellipse];
+    n1 -> n4;
+    n0 -> n1 [label="1"];
+    n5 [label="shoot \"fast\"\nrequires: Shooter", shape=ellipse];
+    n6 [label="Unnamed Command\nrequires: Shooter", shape=ellipse];
+    n5 -> n6;
+    n0 -> n5 [label="2"];
+}
+"#
+    );
+    assert_eq!(
+        tree.to_mermaid_with(&namer),
+        r#"flowchart TD
+    n0["sequence<br/>requires: Drive, Shooter"]
+    n1["race<br/>requires: Drive"]
+    n2(["drive<br/>requires: Drive"])
+    n3(["Unnamed Command<br/>requires: Drive"])
+    n2 --> n3
+    n1 --> n2
+    n4(["TimedCommand(1s)"])
+    n1 --> n4
+    n0 -->|1| n1
+    n5(["shoot #quot;fast#quot;<br/>requires: Shooter"])
+    n6(["Unnamed Command<br/>requires: Shooter"])
+    n5 --> n6
+    n0 -->|2| n5
+"#
+    );
+}
+
+#[test]
+fn test_wpilog() {
+    use super::*;
+    use crate::wpilog::{self, DataLog};
+    use std::io::Write;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct LoggedSubsystem;
+    impl Subsystem for LoggedSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+        fn name(&self) -> &'static str {
+            "Logged"
+        }
+        fn log(&self) {
+            wpilog::append("/Logged/Value", 2.5).expect("Failed to append");
+        }
+    }
+
+    let buffer = SharedBuffer::default();
+    let mut manager = CommandManager::new();
+    manager.set_data_log(DataLog::new(buffer.clone(), "test").expect("Failed to create log"));
+    let subsystem = SubsystemCell::<LoggedSubsystem>::generate(&mut manager);
+
+    CommandBuilder::new()
+        .is_finished(|| true)
+        .with_subsystem(&subsystem)
+        .build()
+        .with_name(&"logged_command")
+        .schedule();
+    manager.run();
+    drop(manager);
+
+    let bytes = buffer.0.borrow().clone();
+    assert_eq!(&bytes[..6], b"WPILOG");
+    assert_eq!(u16::from_le_bytes([bytes[6], bytes[7]]), 0x0100);
+    assert_eq!(
+        u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        4
+    );
+    assert_eq!(&bytes[12..16], b"test");
+
+    // walk the records, collecting started entry names and string payloads
+    let mut names = Vec::new();
+    let mut strings = Vec::new();
+    let mut entries = std::collections::HashMap::new();
+    let mut pos = 16;
+    let read_le = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .rev()
+            .fold(0u64, |acc, b| acc << 8 | u64::from(*b))
+    };
+    while pos < bytes.len() {
+        let header = bytes[pos] as usize;
+        let (id_len, size_len, ts_len) = (
+            (header & 3) + 1,
+            ((header >> 2) & 3) + 1,
+            ((header >> 4) & 7) + 1,
+        );
+        pos += 1;
+        let id = read_le(&bytes[pos..pos + id_len]);
+        pos += id_len;
+        let size = read_le(&bytes[pos..pos + size_len]) as usize;
+        pos += size_len + ts_len;
+        let payload = &bytes[pos..pos + size];
+        pos += size;
+        if id == 0 {
+            assert_eq!(payload[0], 0, "only start records are written");
+            let entry = u32::from_le_bytes(payload[1..5].try_into().unwrap());
+            let len = u32::from_le_bytes(payload[5..9].try_into().unwrap()) as usize;
+            let name = String::from_utf8(payload[9..9 + len].to_vec()).unwrap();
+            entries.insert(u64::from(entry), name.clone());
+            names.push(name);
+        } else if entries[&id].starts_with("/Scheduler/Command")
+            || entries[&id].starts_with("/Scheduler/Subsystems")
+        {
+            strings.push(String::from_utf8(payload.to_vec()).unwrap());
+        } else if entries[&id] == "/Logged/Value" {
+            assert_eq!(f64::from_le_bytes(payload.try_into().unwrap()), 2.5);
+        }
+    }
+    for name in [
+        "/Logged/Value",
+        "/Scheduler/CommandInitialized",
+        "/Scheduler/CommandFinished",
+        "/Scheduler/Subsystems/Logged",
+        "/Scheduler/LoopTime",
+    ] {
+        assert!(names.contains(&name.to_owned()), "Entry {name} not started");
+    }
+    assert!(strings.contains(&"logged_command".to_owned()));
+}
+
+#[cfg(feature = "nt")]
+#[test]
+fn test_scheduler_publisher_cancel() {
+    use super::*;
+    use crate::nt::{InProcessServer, NtValue, SchedulerPublisher};
+    use std::cell::Cell;
+
+    let mut manager = CommandManager::new();
+    let server = InProcessServer::new();
+    let mut publisher = SchedulerPublisher::new(server.clone());
+
+    let interrupted = Rc::new(Cell::new(false));
+    CommandBuilder::new()
+        .end(clone_mv!(
+            interrupted >> |was_interrupted| interrupted.set(was_interrupted)
+        ))
+        .build()
+        .with_name(&"long_running")
+        .schedule();
+    manager.run();
+    publisher.update(&mut manager);
+
+    assert_eq!(
+        server.get("/SmartDashboard/Scheduler/.type"),
+        Some(NtValue::String(String::from("Scheduler")))
+    );
+    let Some(NtValue::IntArray(ids)) = server.get("/SmartDashboard/Scheduler/Ids") else {
+        panic!("Ids not published");
+    };
+    assert_eq!(ids.len(), 1);
+
+    server.set_remote(
+        "/SmartDashboard/Scheduler/Cancel",
+        NtValue::IntArray(ids.clone()),
+    );
+    publisher.update(&mut manager);
+    manager.run();
+    publisher.update(&mut manager);
+
+    assert!(interrupted.get());
+    assert_eq!(
+        server.get("/SmartDashboard/Scheduler/Names"),
+        Some(NtValue::StringArray(Vec::new()))
+    );
+
+    // commands scheduled by bindings are only scheduled again on a new edge
+    Condition::new(|| true).on_true(Command::empty().with_name(&"bound"));
+    manager.run();
+    manager.run();
+    publisher.update(&mut manager);
+    let Some(NtValue::IntArray(bound_ids)) = server.get("/SmartDashboard/Scheduler/Ids") else {
+        panic!("Ids not published");
+    };
+    server.set_remote(
+        "/SmartDashboard/Scheduler/Cancel",
+        NtValue::IntArray(bound_ids),
+    );
+    publisher.update(&mut manager);
+    manager.run();
+    publisher.update(&mut manager);
+    assert_eq!(
+        server.get("/SmartDashboard/Scheduler/Names"),
+        Some(NtValue::StringArray(Vec::new()))
+    );
+
+    // a stale id does not cancel a command that reused its slot
+    Command::empty().with_name(&"reused").schedule();
+    manager.run();
+    publisher.update(&mut manager);
+    assert_eq!(
+        manager.snapshot().commands[0].index,
+        CommandIndex::Command(0)
+    );
+    server.set_remote("/SmartDashboard/Scheduler/Cancel", NtValue::IntArray(ids));
+    publisher.update(&mut manager);
+    manager.run();
+    publisher.update(&mut manager);
+    assert_eq!(
+        server.get("/SmartDashboard/Scheduler/Names"),
+        Some(NtValue::StringArray(vec![String::from("reused")]))
+    );
+}
+
+#[test]
+fn test_tracing_spans() {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::{span, subscriber::Subscriber, Event, Metadata};
+
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<&'static str>>>);
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut names = self.0.lock().expect("recorder poisoned");
+            names.push(span.metadata().name());
+            span::Id::from_u64(names.len() as u64)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let recorder = SpanRecorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let mut manager = CommandManager::new();
+    CommandBuilder::new()
+        .is_finished(|| true)
+        .build()
+        .schedule();
+    manager.run();
+
+    let names = recorder.0.lock().expect("recorder poisoned");
+    for name in [
+        "run",
+        "update",
+        "run_subsystems",
+        "run_cond_schedulers",
+        "run_commands",
+        "command",
+        "init",
+        "periodic",
+        "end",
+    ] {
+        assert!(names.contains(&name), "Span {name} not entered");
+    }
+}
+
+#[test]
+fn test_loop_watchdog() {
+    use super::*;
+    use crate::watchdog::EpochKind;
+    use std::time::Duration;
+
+    let mut manager = CommandManager::new();
+    manager.set_loop_budget(Some(Duration::from_millis(20)));
+
+    // far over budget so timing noise can't hide the overrun, idle cycles may overrun too
+    CommandBuilder::new()
+        .periodic(|_| std::thread::sleep(Duration::from_millis(100)))
+        .is_finished(|| true)
+        .build()
+        .with_name(&"slow")
+        .schedule();
+    manager.run();
+    manager.run();
+
+    let stats = manager.loop_stats();
+    assert_eq!(stats.cycles, 2);
+    assert!(stats.overruns >= 1);
+    assert!(stats.max_loop_time >= Duration::from_millis(100));
+    let worst_command = stats
+        .worst_overrun
+        .iter()
+        .find(|epoch| epoch.kind == EpochKind::Command)
+        .expect("command epoch missing");
+    assert_eq!(worst_command.name, "slow");
+    assert_eq!(
+        stats
+            .last_phases
+            .iter()
+            .map(|phase| phase.name.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "update",
+            "run_subsystems",
+            "run_cond_schedulers",
+            "run_commands"
+        ]
+    );
+}
+
+#[test]
+fn test_robot_runner() {
+    use super::*;
+    use crate::robot::{Robot, RobotMode, RobotRunner};
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    #[derive(Default)]
+    struct Counter {
+        inits: usize,
+        periodics: usize,
+        transitions: Vec<(RobotMode, bool)>,
+    }
+    impl Robot for Counter {
+        fn robot_init(&mut self, _manager: &mut CommandManager) {
+            self.inits += 1;
+        }
+        fn robot_periodic(&mut self, _manager: &mut CommandManager) {
+            self.periodics += 1;
+        }
+        fn mode_init(&mut self, mode: RobotMode, _manager: &mut CommandManager) {
+            self.transitions.push((mode, true));
+        }
+        fn mode_exit(&mut self, mode: RobotMode, _manager: &mut CommandManager) {
+            self.transitions.push((mode, false));
+        }
+    }
+
+    let mode = Rc::new(Cell::new(RobotMode::Disabled));
+    let mode_source = mode.clone();
+    let mut runner = RobotRunner::new(CommandManager::new(), Counter::default())
+        .with_period(Duration::from_millis(20))
+        .mode_source(move || mode_source.get());
+
+    // due on the first cycle, then not for the rest of the test
+    let slow_runs = Rc::new(Cell::new(0));
+    runner.add_periodic(
+        "slow",
+        clone_mv!(slow_runs >> |_dt| slow_runs.set(slow_runs.get() + 1)),
+        Duration::from_secs(3600),
+        Duration::ZERO,
+    );
+
+    for _ in 0..5 {
+        runner.step();
+    }
+    mode.set(RobotMode::Teleop);
+    runner.step();
+
+    let robot = runner.robot();
+    assert_eq!(robot.inits, 1);
+    assert_eq!(robot.periodics, 6);
+    assert_eq!(
+        robot.transitions,
+        [
+            (RobotMode::Disabled, true),
+            (RobotMode::Disabled, false),
+            (RobotMode::Teleop, true)
+        ]
+    );
+    assert_eq!(slow_runs.get(), 1);
+    assert_eq!(runner.mode(), Some(RobotMode::Teleop));
+    assert_eq!(runner.manager().mode(), Some(RobotMode::Teleop));
+
+    // a zero period runs once per cycle instead of spinning between cycles
+    let every_cycle = Rc::new(Cell::new(0));
+    runner.add_periodic(
+        "every cycle",
+        clone_mv!(every_cycle >> |_dt| every_cycle.set(every_cycle.get() + 1)),
+        Duration::ZERO,
+        Duration::ZERO,
+    );
+    runner.run_for(Duration::from_millis(50));
+    assert_eq!(every_cycle.get(), runner.robot().periodics - 6);
+
+    let zero_period = std::panic::catch_unwind(|| {
+        RobotRunner::new(CommandManager::new(), Counter::default()).with_period(Duration::ZERO)
+    });
+    assert!(zero_period.is_err());
+}
+
+#[test]
+fn test_periodic_callbacks() {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    let mut manager = CommandManager::new();
+    let dts = Rc::new(RefCell::new(Vec::new()));
+    let dts_inner = dts.clone();
+    let id = manager.add_periodic(
+        move |dt| {
+            dts_inner.borrow_mut().push(dt);
+            std::thread::sleep(Duration::from_millis(5));
+        },
+        Duration::ZERO,
+        Duration::ZERO,
+    );
+
+    manager.run();
+    manager.run();
+    assert_eq!(dts.borrow().len(), 2);
+    assert_eq!(dts.borrow()[0], Duration::ZERO);
+    // the time spent inside the callback counts towards the next dt
+    assert!(dts.borrow()[1] >= Duration::from_millis(5));
+
+    let slow_runs = Rc::new(RefCell::new(0));
+    let slow_runs_inner = slow_runs.clone();
+    manager.add_periodic(
+        move |_| *slow_runs_inner.borrow_mut() += 1,
+        Duration::from_secs(60),
+        Duration::ZERO,
+    );
+    manager.run();
+    manager.run();
+    assert_eq!(*slow_runs.borrow(), 1);
+
+    assert!(manager.remove_periodic(id));
+    assert!(!manager.remove_periodic(id));
+    manager.run();
+    assert_eq!(dts.borrow().len(), 4);
+}
+
+#[test]
+fn test_priority_preemption() {
+    use super::*;
+
+    struct ArmSubsystem;
+    impl Subsystem for ArmSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let arm = SubsystemCell::<ArmSubsystem>::generate(&mut manager);
+    let hold = |name: &str| {
+        CommandBuilder::new()
+            .with_subsystem(&arm)
+            .build()
+            .with_name(&name)
+    };
+    let running = |manager: &CommandManager| {
+        manager
+            .snapshot()
+            .commands
+            .into_iter()
+            .map(|command| command.name)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(manager
+        .schedule(hold("climb").with_priority(1))
+        .is_scheduled());
+    manager.run();
+    let climb = manager.snapshot().commands[0].index;
+
+    // lower priority is rejected even though climb cancels itself on equal priority
+    assert_eq!(
+        manager.schedule(hold("stow")),
+        ScheduleOutcome::RejectedBy(climb)
+    );
+    manager.run();
+    assert_eq!(running(&manager), ["climb"]);
+
+    // equal priority follows the running command's interruption behavior
+    assert!(manager
+        .schedule(
+            hold("hang")
+                .with_priority(1)
+                .with_interruption_behavior(InterruptionBehavior::CancelIncoming),
+        )
+        .is_scheduled());
+    manager.run();
+    assert_eq!(running(&manager), ["hang"]);
+    let hang = manager.snapshot().commands[0].index;
+    assert_eq!(
+        manager.schedule(hold("climb again").with_priority(1)),
+        ScheduleOutcome::RejectedBy(hang)
+    );
+
+    // higher priority always wins, priority and behavior carry through groups
+    let group = hold("emergency stop")
+        .with_priority(2)
+        .along_with(Command::empty());
+    assert_eq!(group.priority(), 2);
+    assert!(manager.schedule(group).is_scheduled());
+    manager.run();
+    assert_eq!(running(&manager), ["emergency stop,Unnamed Command"]);
+}
+
+#[test]
+fn test_rejected_commands_are_dropped() {
+    use super::*;
+    use crate::{conditions::ConditionalScheduler, robot::RobotMode};
+    use std::cell::Cell;
+
+    struct IntakeSubsystem;
+    impl Subsystem for IntakeSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let intake = SubsystemCell::<IntakeSubsystem>::generate(&mut manager);
+    let outcome = manager.schedule(
+        CommandBuilder::new()
+            .with_subsystem(&intake)
+            .build()
+            .with_interruption_behavior(InterruptionBehavior::CancelIncoming),
+    );
+    let ScheduleOutcome::Scheduled(holder) = outcome else {
+        panic!("The intake is free but got {outcome:?}");
+    };
+    manager.run();
+
+    // every rejected command has to be dropped instead of piling up in the manager
+    let token = Rc::new(());
+    for _ in 0..10 {
+        let held = token.clone();
+        let command = CommandBuilder::new()
+            .init(move || assert!(Rc::strong_count(&held) > 1))
+            .with_subsystem(&intake)
+            .build();
+        assert_eq!(
+            manager.schedule(command),
+            ScheduleOutcome::RejectedBy(holder)
+        );
+        manager.run();
+    }
+    assert_eq!(Rc::strong_count(&token), 1);
+    assert_eq!(manager.snapshot().commands.len(), 1);
+
+    // a binding that keeps firing while its command runs leaves it running undisturbed
+    let inits = Rc::new(Cell::new(0));
+    manager.add_cond_scheduler(ConditionalScheduler::new(
+        Condition::new(|| true),
+        CommandBuilder::new()
+            .init(clone_mv!(inits >> || inits.set(inits.get() + 1)))
+            .build(),
+        None,
+    ));
+    for _ in 0..3 {
+        manager.run();
+    }
+    assert_eq!(inits.get(), 1);
+
+    manager.set_mode(RobotMode::Disabled);
+    let held = token.clone();
+    assert_eq!(
+        manager.schedule(
+            CommandBuilder::new()
+                .init(move || assert!(Rc::strong_count(&held) > 1))
+                .build()
+        ),
+        ScheduleOutcome::Disabled
+    );
+    assert_eq!(Rc::strong_count(&token), 1);
+    assert!(manager
+        .schedule(Command::empty().ignoring_disable(true))
+        .is_scheduled());
+}
+
+#[test]
+fn test_reschedule_pending_interrupt() {
+    use super::*;
+    use crate::conditions::EventLoop;
+    use std::cell::{Cell, RefCell};
+
+    struct IntakeSubsystem;
+    impl Subsystem for IntakeSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let intake = SubsystemCell::<IntakeSubsystem>::generate(&mut manager);
+    let event_loop = EventLoop::new();
+
+    let pressed = Rc::new(Cell::new(true));
+    let inits = Rc::new(Cell::new(0));
+    let ends = Rc::new(RefCell::new(Vec::new()));
+    Condition::new(clone_mv!(pressed >> || pressed.get()))
+        .with_event_loop(event_loop)
+        .on_true(
+            CommandBuilder::new()
+                .with_subsystem(&intake)
+                .init(clone_mv!(inits >> || inits.set(inits.get() + 1)))
+                .end(clone_mv!(
+                    ends >> |interrupted| ends.borrow_mut().push(interrupted)
+                ))
+                .build(),
+        );
+    let press = |manager: &mut CommandManager| {
+        pressed.set(false);
+        manager.poll_event_loop(event_loop);
+        pressed.set(true);
+        manager.poll_event_loop(event_loop);
+    };
+    manager.run();
+    manager.poll_event_loop(event_loop);
+    manager.run();
+    assert_eq!(inits.get(), 1);
+    let index = manager.snapshot().commands[0].index;
+
+    // a new edge before the interrupt is handled does not undo the cancel
+    assert!(manager.cancel(index));
+    press(&mut manager);
+    manager.run();
+    assert_eq!(*ends.borrow(), [true]);
+    assert!(manager.snapshot().commands.is_empty());
+
+    // the next edge initializes it again
+    press(&mut manager);
+    manager.run();
+    assert_eq!(inits.get(), 2);
+}
+
+#[test]
+fn test_parallel_requirement_conflicts() {
+    use super::*;
+    use std::time::Duration;
+
+    struct ShooterSubsystem;
+    impl Subsystem for ShooterSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+    struct FeederSubsystem;
+    impl Subsystem for FeederSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let shooter = SubsystemCell::<ShooterSubsystem>::generate(&mut manager);
+    let feeder = SubsystemCell::<FeederSubsystem>::generate(&mut manager);
+    let spin = || CommandBuilder::new().with_subsystem(&shooter).build();
+    let feed = || CommandBuilder::new().with_subsystem(&feeder).build();
+
+    assert!(spin().try_along_with(feed()).is_ok());
+    assert!(Command::try_race(vec![spin(), feed(), Command::wait_for(Duration::ZERO)]).is_ok());
+    // sequences may reuse subsystems
+    let _ = spin().before(spin());
+
+    let conflict = Err(CompositionError::OverlappingRequirements(shooter.suid()));
+    assert_eq!(spin().try_along_with(spin()).map(|_| ()), conflict);
+    assert_eq!(
+        spin()
+            .try_race_with_many(vec![feed(), feed().with_extra_requirements(&[&shooter])])
+            .map(|_| ()),
+        Err(CompositionError::OverlappingRequirements(feeder.suid()))
+    );
+    assert_eq!(
+        Command::try_parallel(vec![spin(), spin()]).map(|_| ()),
+        conflict
+    );
+
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = spin().race_with(spin());
+    }));
+    assert!(panic.is_err());
+}
+
+#[test]
+fn test_panic_isolation() {
+    use super::*;
+    use crate::conditions::BooleanSupplier;
+    use std::{cell::Cell, time::Duration};
+
+    thread_local! {
+        static FLAKY_RUNS: Cell<usize> = const { Cell::new(0) };
+    }
+    struct FlakySubsystem;
+    impl Subsystem for FlakySubsystem {
+        fn construct() -> Self {
+            Self
+        }
+        fn periodic(&self, _: Duration) {
+            FLAKY_RUNS.with(|runs| runs.set(runs.get() + 1));
+            panic!("sensor unplugged");
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    manager.set_catch_panics(true);
+    let _flaky = SubsystemCell::<FlakySubsystem>::generate(&mut manager);
+
+    let ended = Rc::new(Cell::new(None));
+    CommandBuilder::new()
+        .periodic(|_| panic!("bad math"))
+        .end(clone_mv!(
+            ended >> |interrupted| ended.set(Some(interrupted))
+        ))
+        .build()
+        .with_name(&"broken")
+        .schedule();
+    let survivor_runs = Rc::new(Cell::new(0));
+    let runs = survivor_runs.clone();
+    CommandBuilder::new()
+        .periodic(move |_| runs.set(runs.get() + 1))
+        .build()
+        .with_name(&"survivor")
+        .schedule();
+    let broken_interrupted = manager.command_interrupted("broken");
+
+    manager.run();
+    assert_eq!(ended.get(), Some(true));
+    assert!(broken_interrupted.get_as_boolean());
+    manager.run();
+    assert_eq!(survivor_runs.get(), 2);
+    assert_eq!(FLAKY_RUNS.with(Cell::get), 2);
+    let names = manager
+        .snapshot()
+        .commands
+        .into_iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["survivor"]);
+}
+
+#[test]
+fn test_fallible_commands() {
+    use super::*;
+    use std::{cell::Cell, cell::RefCell, fmt};
+
+    #[derive(Debug)]
+    struct CanTimeout;
+    impl fmt::Display for CanTimeout {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "CAN timeout")
+        }
+    }
+    impl std::error::Error for CanTimeout {}
+
+    let mut manager = CommandManager::new();
+    let reported = Rc::new(RefCell::new(Vec::new()));
+    let errors = reported.clone();
+    manager.set_error_handler(move |name, error| {
+        errors.borrow_mut().push(format!("{name}: {error}"));
+    });
+
+    let polls = Rc::new(Cell::new(0));
+    let later_ran = Rc::new(Cell::new(false));
+    let ended = Rc::new(Cell::new(None));
+    let faulty = CommandBuilder::new()
+        .try_periodic(clone_mv!(
+            polls
+                >> |_period| {
+                    polls.set(polls.get() + 1);
+                    if polls.get() < 2 {
+                        Ok(())
+                    } else {
+                        Err(CanTimeout)
+                    }
+                }
+        ))
+        .end(clone_mv!(
+            ended >> |interrupted| ended.set(Some(interrupted))
+        ))
+        .build()
+        .with_name(&"read encoder");
+    let sequence = faulty
+        .along_with(Command::empty())
+        .before(
+            CommandBuilder::new()
+                .init(clone_mv!(later_ran >> || later_ran.set(true)))
+                .build(),
+        )
+        .with_name(&"auto");
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(sequence) else {
+        panic!("Nothing else is running");
+    };
+
+    manager.run();
+    assert!(manager.command_error(index).is_none());
+    manager.run();
+    assert_eq!(ended.get(), Some(true));
+    assert!(!later_ran.get());
+    assert!(manager.snapshot().commands.is_empty());
+    assert_eq!(*reported.borrow(), ["auto: CAN timeout"]);
+    assert_eq!(
+        manager.command_error(index).map(ToString::to_string),
+        Some(String::from("CAN timeout"))
+    );
+
+    // init errors end the command before periodic runs
+    let periodic_ran = Rc::new(Cell::new(false));
+    let command = CommandBuilder::new()
+        .try_init(|| Err("motor controller missing"))
+        .periodic(clone_mv!(periodic_ran >> |_period| periodic_ran.set(true)))
+        .build();
+    assert!(manager.schedule(command).is_scheduled());
+    manager.run();
+    assert!(!periodic_ran.get());
+    assert_eq!(reported.borrow().len(), 2);
+}
+
+#[test]
+fn test_value_commands() {
+    use super::*;
+    use crate::snapshot::CommandKind;
+    use std::cell::{Cell, RefCell};
+
+    struct TurretSubsystem;
+    impl Subsystem for TurretSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let turret = SubsystemCell::<TurretSubsystem>::generate(&mut manager);
+
+    let frames = Rc::new(RefCell::new(vec![Some(12.5), None, None]));
+    let aimed_at = Rc::new(Cell::new(None));
+    let find_target = ValueCommand::poll(
+        clone_mv!(frames >> || frames.borrow_mut().pop().flatten()),
+        &[&turret],
+    );
+    let target = find_target.handle();
+    find_target
+        .and_then_with(
+            clone_mv!(
+                aimed_at
+                    >> |angle| {
+                        CommandBuilder::new()
+                            .init(clone_mv!(aimed_at >> || aimed_at.set(Some(angle))))
+                            .is_finished(|| true)
+                            .build()
+                    }
+            ),
+            &[&turret],
+        )
+        .schedule();
+
+    manager.run();
+    manager.run();
+    assert_eq!(aimed_at.get(), None);
+    manager.run();
+    // the value is handed to the next command as soon as the search finishes
+    assert_eq!(aimed_at.get(), Some(12.5));
+    assert!(!target.is_ready());
+    manager.run();
+    assert!(manager.snapshot().commands.is_empty());
+
+    // the follow-up's requirements are known before it is built
+    let chained =
+        ValueCommand::poll(|| Some(1), &[]).and_then_with(|_| Command::empty(), &[&turret]);
+    assert_eq!(chained.get_requirements(), vec![turret.suid()]);
+
+    // value commands show the structure of the command they wrap
+    let tree = Command::from(ValueCommand::new(
+        Command::empty().with_name(&"measure"),
+        || 0,
+    ))
+    .tree();
+    assert_eq!(tree.kind, CommandKind::Named);
+    assert_eq!(tree.name, "measure");
+
+    // an interrupted value command produces nothing
+    let measured = ValueCommand::new(
+        CommandBuilder::new().with_subsystem(&turret).build(),
+        || "offset",
+    );
+    let offset = measured.handle();
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(measured.into()) else {
+        panic!("The turret is free");
+    };
+    manager.run();
+    assert!(manager.cancel(index));
+    manager.run();
+    assert_eq!(offset.take(), None);
+
+    let measured = ValueCommand::new(CommandBuilder::new().is_finished(|| true).build(), || {
+        "offset"
+    });
+    let offset = measured.handle();
+    measured.schedule();
+    manager.run();
+    assert_eq!(offset.take(), Some("offset"));
+}
+
+#[test]
+fn test_async_commands() {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    struct IntakeSubsystem;
+    impl Subsystem for IntakeSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let intake = SubsystemCell::<IntakeSubsystem>::generate(&mut manager);
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let step = |log: &Rc<RefCell<Vec<&'static str>>>, marker: &'static str| {
+        CommandBuilder::new()
+            .init(clone_mv!(log >> || log.borrow_mut().push(marker)))
+            .is_finished(|| true)
+            .build()
+    };
+    let has_note = Rc::new(Cell::new(false));
+    let routine = {
+        let (log, has_note) = (log.clone(), has_note.clone());
+        async move {
+            step(&log, "deploy").await?;
+            future::wait_until(|| has_note.get()).await;
+            step(&log, "stow").await
+        }
+    };
+    let command = Command::from_async(routine, &[&intake]);
+    // requirements are declared up front
+    assert_eq!(command.get_requirements(), [intake.suid()]);
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(command) else {
+        panic!("The intake is free");
+    };
+
+    manager.run();
+    manager.run();
+    assert_eq!(*log.borrow(), ["deploy"]);
+    has_note.set(true);
+    manager.run();
+    assert_eq!(*log.borrow(), ["deploy", "stow"]);
+    assert!(!manager.cancel(index));
+
+    // cancelling drops the future, interrupting the awaited command
+    let ended = Rc::new(Cell::new(None));
+    let routine = CommandBuilder::new()
+        .end(clone_mv!(
+            ended >> |interrupted| ended.set(Some(interrupted))
+        ))
+        .build();
+    let ScheduleOutcome::Scheduled(index) =
+        manager.schedule(Command::from_async(async move { routine.await }, &[]))
+    else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert!(manager.cancel(index));
+    manager.run();
+    assert_eq!(ended.get(), Some(true));
+
+    // errors from the future or awaited commands end the command,
+    // a command that fails to init is not run
+    let ran = Rc::new(Cell::new(false));
+    let command = Command::from_async_fn(
+        clone_mv!(
+            ran >> || {
+                let ran = ran.clone();
+                async move {
+                    CommandBuilder::new()
+                        .try_init(|| Err("no note detected"))
+                        .periodic(move |_| ran.set(true))
+                        .build()
+                        .await
+                }
+            }
+        ),
+        &[],
+    );
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(command) else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert_eq!(
+        manager.command_error(index).map(ToString::to_string),
+        Some(String::from("no note detected"))
+    );
+    assert!(!ran.get());
+
+    // an error that is handled inside the future does not end it
+    let command = Command::from_async(
+        async {
+            let failed = CommandBuilder::new()
+                .try_init(|| Err("no note detected"))
+                .build()
+                .await;
+            assert!(failed.is_err());
+        },
+        &[],
+    );
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(command) else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert!(manager.command_error(index).is_none());
+    assert!(manager.snapshot().commands.is_empty());
+    let ScheduleOutcome::Scheduled(index) =
+        manager.schedule(Command::from_async(async { Err("jammed") }, &[]))
+    else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert!(manager.command_error(index).is_some());
+}
+
+#[test]
+fn test_fsm_command() {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Shooter {
+        Intaking,
+        Indexing,
+        Shooting,
+        Stowed,
+    }
+
+    let mut manager = CommandManager::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let tracked = |log: &Rc<RefCell<Vec<String>>>, name: &'static str, finishes: bool| {
+        CommandBuilder::new()
+            .init(clone_mv!(
+                log >> || log.borrow_mut().push(format!("{name} init"))
+            ))
+            .end(clone_mv!(
+                log >> |interrupted| {
+                    log.borrow_mut().push(format!("{name} end {interrupted}"));
+                }
+            ))
+            .is_finished(move || finishes)
+            .build()
+    };
+    let has_note = Rc::new(Cell::new(false));
+    let abort = Rc::new(Cell::new(false));
+    let fsm = FsmCommand::new(Shooter::Intaking)
+        .with_name(&"shooter")
+        .state(Shooter::Intaking, tracked(&log, "intake", false))
+        .state(Shooter::Indexing, tracked(&log, "index", false))
+        .state(Shooter::Shooting, tracked(&log, "shoot", true))
+        .transition(
+            Shooter::Intaking,
+            Shooter::Indexing,
+            clone_mv!(has_note >> || has_note.get()),
+        )
+        .transition(Shooter::Indexing, Shooter::Shooting, || true)
+        .transition_on_finish(Shooter::Shooting, Shooter::Intaking)
+        .transition_from_any(Shooter::Stowed, clone_mv!(abort >> || abort.get()))
+        .final_state(Shooter::Stowed);
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(fsm.into()) else {
+        panic!("Nothing else is running");
+    };
+
+    let name = |manager: &CommandManager| manager.snapshot().commands[0].name.clone();
+    manager.run();
+    assert_eq!(name(&manager), "shooter[Intaking]");
+    manager.run();
+    has_note.set(true);
+    manager.run();
+    assert_eq!(name(&manager), "shooter[Indexing]");
+    has_note.set(false);
+    manager.run();
+    manager.run();
+    // the shot finishes on its own and loops back to intaking
+    assert_eq!(name(&manager), "shooter[Intaking]");
+    assert_eq!(
+        *log.borrow(),
+        [
+            "intake init",
+            "intake end true",
+            "index init",
+            "index end true",
+            "shoot init",
+            "shoot end false",
+            "intake init",
+        ]
+    );
+
+    abort.set(true);
+    manager.run();
+    assert_eq!(
+        log.borrow().last().map(String::as_str),
+        Some("intake end true")
+    );
+    assert!(!manager.cancel(index));
+
+    // interrupting the state machine ends the current state's command
+    abort.set(false);
+    log.borrow_mut().clear();
+    let fsm =
+        FsmCommand::new(Shooter::Intaking).state(Shooter::Intaking, tracked(&log, "intake", false));
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(fsm.into()) else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert!(manager.cancel(index));
+    manager.run();
+    assert_eq!(*log.borrow(), ["intake init", "intake end true"]);
+
+    // states without a command never finish, only conditions leave them
+    let leave = Rc::new(Cell::new(false));
+    FsmCommand::new(Shooter::Intaking)
+        .transition_on_finish(Shooter::Intaking, Shooter::Shooting)
+        .transition(
+            Shooter::Intaking,
+            Shooter::Stowed,
+            clone_mv!(leave >> || leave.get()),
+        )
+        .final_state(Shooter::Stowed)
+        .schedule();
+    for _ in 0..3 {
+        manager.run();
+        assert_eq!(name(&manager), "FSM[Intaking]");
+    }
+    leave.set(true);
+    manager.run();
+    manager.run();
+    assert!(manager.snapshot().commands.is_empty());
+}
+
+#[test]
+fn test_behavior_tree() {
+    use super::*;
+    use behavior_tree::{Node, Status, TreeFailure};
+    use std::cell::{Cell, RefCell};
+
+    struct DriveSubsystem;
+    impl Subsystem for DriveSubsystem {
+        fn construct() -> Self {
+            Self
+        }
+    }
+
+    let mut manager = CommandManager::new();
+    let drive = SubsystemCell::<DriveSubsystem>::generate(&mut manager);
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let tracked = |log: &Rc<RefCell<Vec<String>>>, name: &'static str| {
+        CommandBuilder::new()
+            .init(clone_mv!(
+                log >> || log.borrow_mut().push(format!("{name} init"))
+            ))
+            .end(clone_mv!(
+                log >> |interrupted| {
+                    log.borrow_mut().push(format!("{name} end {interrupted}"));
+                }
+            ))
+    };
+
+    // a reactive selector halts the lower priority branch when a higher one takes over
+    let sees_target = Rc::new(Cell::new(false));
+    let tree = Node::selector(vec![
+        Node::sequence(vec![
+            Node::condition(clone_mv!(sees_target >> || sees_target.get())),
+            tracked(&log, "aim").with_subsystem(&drive).build().into(),
+        ]),
+        tracked(&log, "search").build().into(),
+    ])
+    .into_command();
+    assert_eq!(tree.get_requirements(), [drive.suid()]);
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(tree) else {
+        panic!("The drivetrain is free");
+    };
+    manager.run();
+    manager.run();
+    sees_target.set(true);
+    manager.run();
+    assert_eq!(
+        *log.borrow(),
+        ["search init", "aim init", "search end true"]
+    );
+    assert!(manager.cancel(index));
+    manager.run();
+    assert_eq!(
+        log.borrow().last().map(String::as_str),
+        Some("aim end true")
+    );
+
+    // failing leaves make the sequence fail, retries start it over
+    log.borrow_mut().clear();
+    let attempts = Rc::new(Cell::new(0));
+    let tree = Node::sequence(vec![
+        tracked(&log, "spin up").is_finished(|| true).build().into(),
+        Node::leaf(clone_mv!(
+            attempts
+                >> |_period| {
+                    attempts.set(attempts.get() + 1);
+                    Status::Failure
+                }
+        )),
+        tracked(&log, "shoot").is_finished(|| true).build().into(),
+    ])
+    .retry(2)
+    .into_command();
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(tree) else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    manager.run();
+    assert_eq!(attempts.get(), 2);
+    assert_eq!(
+        *log.borrow(),
+        [
+            "spin up init",
+            "spin up end false",
+            "spin up init",
+            "spin up end false"
+        ]
+    );
+    assert!(manager
+        .command_error(index)
+        .is_some_and(|error| error.is::<TreeFailure>()));
+
+    // a command error is a failure the fallback recovers from
+    let recovered = Rc::new(Cell::new(false));
+    let tree = Node::fallback(vec![
+        CommandBuilder::new()
+            .try_init(|| Err("camera disconnected"))
+            .build()
+            .into(),
+        Node::leaf(clone_mv!(
+            recovered
+                >> |_period| {
+                    recovered.set(true);
+                    Status::Success
+                }
+        )),
+    ])
+    .into_command();
+    let ScheduleOutcome::Scheduled(index) = manager.schedule(tree) else {
+        panic!("Nothing else is running");
+    };
+    manager.run();
+    assert!(recovered.get());
+    assert!(manager.command_error(index).is_none());
+    assert!(manager.snapshot().commands.is_empty());
+}
+
+#[test]
+fn test_race_interrupts_remaining_members() {
+    use super::*;
+    use std::cell::RefCell;
+
+    let mut manager = CommandManager::new();
+    let ends = Rc::new(RefCell::new(Vec::new()));
+    let member = |name: &'static str, finishes: bool| {
+        CommandBuilder::new()
+            .end(clone_mv!(
+                ends >> |interrupted| ends.borrow_mut().push((name, interrupted))
+            ))
+            .is_finished(move || finishes)
+            .build()
+    };
+    member("timeout", true)
+        .race_with(member("spin up", false))
+        .schedule();
+    manager.run();
+    manager.run();
+    assert_eq!(*ends.borrow(), [("timeout", false), ("spin up", true)]);
+    assert!(manager.snapshot().commands.is_empty());
+}
+
+#[test]
+fn test_group_macros() {
+    use super::*;
+    use crate::snapshot::{CommandKind, ParallelKind};
+    use std::cell::{Cell, RefCell};
+
+    let mut manager = CommandManager::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let tracked = |log: &Rc<RefCell<Vec<String>>>, name: &'static str, cycles: usize| {
+        let ran = Rc::new(Cell::new(0));
+        CommandBuilder::new()
+            .init(clone_mv!(
+                log >> || log.borrow_mut().push(format!("{name} init"))
+            ))
+            .periodic(clone_mv!(ran >> |_period| ran.set(ran.get() + 1)))
+            .end(clone_mv!(
+                log >> |interrupted| {
+                    log.borrow_mut().push(format!("{name} end {interrupted}"));
+                }
+            ))
+            .is_finished(move || ran.get() >= cycles)
+    };
+
+    let auto = sequence![
+        tracked(&log, "score", 1),
+        deadline!(
+            tracked(&log, "drive", 2).build(),
+            tracked(&log, "intake", 5),
+            race![tracked(&log, "spin up", 1), Command::empty()],
+        ),
+        parallel![
+            ValueCommand::new(tracked(&log, "aim", 1).build(), || 0.5),
+            tracked(&log, "feed", 1),
+        ],
+    ];
+    let tree = auto.tree();
+    assert_eq!(
+        tree.children[1].kind,
+        CommandKind::Parallel(ParallelKind::Deadline)
+    );
+    assert_eq!(
+        tree.children[1].children[2].kind,
+        CommandKind::Parallel(ParallelKind::Race)
+    );
+    assert_eq!(
+        tree.children[2].kind,
+        CommandKind::Parallel(ParallelKind::All)
+    );
+
+    auto.schedule();
+    for _ in 0..4 {
+        manager.run();
+    }
+    assert_eq!(
+        *log.borrow(),
+        [
+            "score init",
+            "score end false",
+            "drive init",
+            "intake init",
+            "spin up init",
+            "spin up end false",
+            "drive end false",
+            // members left running when the deadline finishes are interrupted
+            "intake end true",
+            "aim init",
+            "feed init",
+            "aim end false",
+            "feed end false",
+        ]
+    );
+    assert!(manager.snapshot().commands.is_empty());
+}
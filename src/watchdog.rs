@@ -0,0 +1,138 @@
+//! Loop timing and overrun reporting, similar to WPILib's `Watchdog` and `Tracer`.
+//!
+//! Enabled by giving the command manager a loop budget with
+//! [`CommandManager::set_loop_budget`](crate::CommandManager::set_loop_budget),
+//! the results can be read back through [`CommandManager::loop_stats`](crate::CommandManager::loop_stats).
+
+use std::{cmp::Reverse, fmt::Write, time::Duration};
+
+/// How many epochs are listed in an overrun report.
+const REPORTED_EPOCHS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EpochKind {
+    /// A phase of [`CommandManager::run`](crate::CommandManager::run).
+    Phase,
+    /// A periodic callback, such as a subsystem's `periodic` and `log`.
+    Periodic,
+    /// All callbacks of a single command in a cycle.
+    Command,
+}
+
+/// The time spent in one part of a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Epoch {
+    pub kind: EpochKind,
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Loop timing statistics collected by the command manager.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoopStats {
+    /// The loop budget overruns are measured against.
+    pub budget: Option<Duration>,
+    /// Cycles run since the budget was set.
+    pub cycles: u64,
+    /// Cycles that took longer than the budget.
+    pub overruns: u64,
+    pub last_loop_time: Duration,
+    pub max_loop_time: Duration,
+    /// The phases of the last cycle, in the order they ran.
+    pub last_phases: Vec<Epoch>,
+    /// The periodic callbacks and commands of the last cycle, slowest first.
+    pub last_epochs: Vec<Epoch>,
+    /// The phases, periodic callbacks and commands of the slowest overrun, slowest first.
+    pub worst_overrun: Vec<Epoch>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Watchdog {
+    stats: LoopStats,
+    phases: Vec<Epoch>,
+    epochs: Vec<Epoch>,
+    worst_overrun_time: Duration,
+}
+impl Watchdog {
+    pub const fn enabled(&self) -> bool {
+        self.stats.budget.is_some()
+    }
+
+    pub fn set_budget(&mut self, budget: Option<Duration>) {
+        *self = Self::default();
+        self.stats.budget = budget;
+    }
+
+    pub const fn stats(&self) -> &LoopStats {
+        &self.stats
+    }
+
+    pub fn add_phase(&mut self, name: &str, duration: Duration) {
+        if self.enabled() {
+            self.phases.push(Epoch {
+                kind: EpochKind::Phase,
+                name: name.to_owned(),
+                duration,
+            });
+        }
+    }
+
+    pub fn add_epoch(
+        &mut self,
+        kind: EpochKind,
+        name: impl FnOnce() -> String,
+        duration: Duration,
+    ) {
+        if self.enabled() {
+            self.epochs.push(Epoch {
+                kind,
+                name: name(),
+                duration,
+            });
+        }
+    }
+
+    pub fn finish_cycle(&mut self, loop_time: Duration) {
+        let Some(budget) = self.stats.budget else {
+            return;
+        };
+        let mut phases = std::mem::take(&mut self.phases);
+        let mut epochs = std::mem::take(&mut self.epochs);
+        epochs.sort_by_key(|epoch| Reverse(epoch.duration));
+
+        self.stats.cycles += 1;
+        self.stats.last_loop_time = loop_time;
+        self.stats.max_loop_time = self.stats.max_loop_time.max(loop_time);
+        if loop_time > budget {
+            self.stats.overruns += 1;
+            let mut breakdown = phases.iter().chain(&epochs).cloned().collect::<Vec<_>>();
+            breakdown.sort_by_key(|epoch| Reverse(epoch.duration));
+            tracing::warn!(
+                "Loop overrun: {loop_time:?} > {budget:?} budget, worst offenders: {}",
+                format_offenders(&breakdown)
+            );
+            if loop_time > self.worst_overrun_time {
+                self.worst_overrun_time = loop_time;
+                self.stats.worst_overrun = breakdown;
+            }
+        }
+        // hand the old buffers back to be reused next cycle
+        std::mem::swap(&mut self.stats.last_phases, &mut phases);
+        std::mem::swap(&mut self.stats.last_epochs, &mut epochs);
+        phases.clear();
+        epochs.clear();
+        self.phases = phases;
+        self.epochs = epochs;
+    }
+}
+
+fn format_offenders(epochs: &[Epoch]) -> String {
+    let mut out = String::new();
+    for (i, epoch) in epochs.iter().take(REPORTED_EPOCHS).enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        let _ = write!(out, "{:?} {} {:?}", epoch.kind, epoch.name, epoch.duration);
+    }
+    out
+}
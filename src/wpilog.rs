@@ -0,0 +1,392 @@
+//! A writer for the `.wpilog` data log format read by AdvantageScope and DataLogTool.
+//!
+//! A [`DataLog`] is installed on a command manager with
+//! [`CommandManager::set_data_log`](crate::CommandManager::set_data_log),
+//! the manager then logs command and subsystem events and loop timing to it.
+//! Subsystems can append their own entries from [`Subsystem::log`](crate::Subsystem::log)
+//! through [`append`].
+//!
+//! # Examples
+//! ```no_run
+//! use frclib_commands::{wpilog::{self, DataLog}, CommandManager, Subsystem};
+//!
+//! struct Drive {
+//!     speed: f64,
+//! }
+//! impl Subsystem for Drive {
+//!     fn construct() -> Self {
+//!         Self { speed: 0.0 }
+//!     }
+//!
+//!     fn log(&self) {
+//!         let _ = wpilog::append("/Drive/Speed", self.speed);
+//!     }
+//! }
+//!
+//! let mut manager = CommandManager::new();
+//! manager.set_data_log(DataLog::create("robot.wpilog").expect("Failed to create log"));
+//! ```
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use fxhash::FxHashMap;
+use thiserror::Error;
+
+thread_local! {
+    static DATA_LOG: RefCell<Option<DataLog>> = const { RefCell::new(None) };
+}
+
+const CONTROL_START: u8 = 0;
+const CONTROL_FINISH: u8 = 1;
+const CONTROL_SET_METADATA: u8 = 2;
+
+#[derive(Debug, Error)]
+pub enum DataLogError {
+    #[error("No data log installed on this thread")]
+    NoDataLog,
+    #[error("Entry {entry} was started as {expected} but was given {found}")]
+    TypeMismatch {
+        entry: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("Entry {0} has not been started")]
+    UnknownEntry(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A value that can be appended to a [`DataLog`] entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogValue {
+    Boolean(bool),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Raw(Vec<u8>),
+    BooleanArray(Vec<bool>),
+    Int64Array(Vec<i64>),
+    FloatArray(Vec<f32>),
+    DoubleArray(Vec<f64>),
+    StringArray(Vec<String>),
+}
+impl LogValue {
+    /// The wpilog type string of this value.
+    #[must_use]
+    pub const fn type_str(&self) -> &'static str {
+        match self {
+            Self::Boolean(_) => "boolean",
+            Self::Int64(_) => "int64",
+            Self::Float(_) => "float",
+            Self::Double(_) => "double",
+            Self::String(_) => "string",
+            Self::Raw(_) => "raw",
+            Self::BooleanArray(_) => "boolean[]",
+            Self::Int64Array(_) => "int64[]",
+            Self::FloatArray(_) => "float[]",
+            Self::DoubleArray(_) => "double[]",
+            Self::StringArray(_) => "string[]",
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Boolean(value) => vec![u8::from(*value)],
+            Self::Int64(value) => value.to_le_bytes().to_vec(),
+            Self::Float(value) => value.to_le_bytes().to_vec(),
+            Self::Double(value) => value.to_le_bytes().to_vec(),
+            Self::String(value) => value.as_bytes().to_vec(),
+            Self::Raw(value) => value.clone(),
+            Self::BooleanArray(values) => values.iter().map(|value| u8::from(*value)).collect(),
+            Self::Int64Array(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Self::FloatArray(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Self::DoubleArray(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Self::StringArray(values) => {
+                let mut payload = Vec::new();
+                push_u32(&mut payload, values.len());
+                for value in values {
+                    push_str(&mut payload, value);
+                }
+                payload
+            }
+        }
+    }
+}
+
+macro_rules! log_value_from {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for LogValue {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+log_value_from!(
+    bool => Boolean,
+    i64 => Int64,
+    i32 => Int64,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+    &str => String,
+    Vec<bool> => BooleanArray,
+    Vec<i64> => Int64Array,
+    Vec<f32> => FloatArray,
+    Vec<f64> => DoubleArray,
+    Vec<String> => StringArray,
+);
+
+#[allow(clippy::cast_possible_truncation)]
+fn push_u32(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn push_str(buf: &mut Vec<u8>, value: &str) {
+    push_u32(buf, value.len());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// The number of little endian bytes needed to store `value`, at least 1.
+const fn byte_len(value: u64) -> usize {
+    let bits = u64::BITS - value.leading_zeros();
+    if bits == 0 {
+        1
+    } else {
+        bits.div_ceil(8) as usize
+    }
+}
+
+/// A `.wpilog` file being written.
+///
+/// Entries are started the first time they are appended to,
+/// with the type of the first value appended.
+pub struct DataLog {
+    writer: Box<dyn Write>,
+    entries: FxHashMap<String, (u32, &'static str)>,
+    next_id: u32,
+    start: Instant,
+}
+impl std::fmt::Debug for DataLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataLog")
+            .field("writer", &"dyn Write")
+            .field("entries", &self.entries)
+            .finish_non_exhaustive()
+    }
+}
+impl DataLog {
+    /// Starts a new data log in the given writer, writing the file header.
+    ///
+    /// # Errors
+    /// - [`DataLogError::Io`] if the header could not be written.
+    pub fn new(writer: impl Write + 'static, extra_header: &str) -> Result<Self, DataLogError> {
+        let mut writer: Box<dyn Write> = Box::new(writer);
+        let mut header = Vec::with_capacity(12 + extra_header.len());
+        header.extend_from_slice(b"WPILOG");
+        header.extend_from_slice(&0x0100u16.to_le_bytes());
+        push_str(&mut header, extra_header);
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            entries: FxHashMap::default(),
+            next_id: 1,
+            start: Instant::now(),
+        })
+    }
+
+    /// Creates a buffered data log file at the given path.
+    ///
+    /// # Errors
+    /// - [`DataLogError::Io`] if the file could not be created.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, DataLogError> {
+        Self::new(BufWriter::new(File::create(path)?), "")
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn timestamp(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    fn write_record(&mut self, entry: u32, payload: &[u8]) -> Result<(), DataLogError> {
+        let timestamp = self.timestamp();
+        let id_len = byte_len(u64::from(entry));
+        let size_len = byte_len(payload.len() as u64);
+        let ts_len = byte_len(timestamp);
+        #[allow(clippy::cast_possible_truncation)]
+        let header = ((id_len - 1) | (size_len - 1) << 2 | (ts_len - 1) << 4) as u8;
+
+        let mut record = Vec::with_capacity(1 + id_len + size_len + ts_len + payload.len());
+        record.push(header);
+        record.extend_from_slice(&entry.to_le_bytes()[..id_len]);
+        record.extend_from_slice(&(payload.len() as u64).to_le_bytes()[..size_len]);
+        record.extend_from_slice(&timestamp.to_le_bytes()[..ts_len]);
+        record.extend_from_slice(payload);
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+
+    /// Starts an entry with the given name, type string and metadata, returning its id.
+    /// Starting an entry that already exists returns the existing id.
+    ///
+    /// # Errors
+    /// - [`DataLogError::TypeMismatch`] if the entry exists with a different type.
+    /// - [`DataLogError::Io`] if the record could not be written.
+    pub fn start_entry(
+        &mut self,
+        name: &str,
+        type_str: &'static str,
+        metadata: &str,
+    ) -> Result<u32, DataLogError> {
+        if let Some((id, existing)) = self.entries.get(name) {
+            if *existing != type_str {
+                return Err(DataLogError::TypeMismatch {
+                    entry: name.to_owned(),
+                    expected: existing,
+                    found: type_str,
+                });
+            }
+            return Ok(*id);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut payload = vec![CONTROL_START];
+        payload.extend_from_slice(&id.to_le_bytes());
+        push_str(&mut payload, name);
+        push_str(&mut payload, type_str);
+        push_str(&mut payload, metadata);
+        self.write_record(0, &payload)?;
+        self.entries.insert(name.to_owned(), (id, type_str));
+        Ok(id)
+    }
+
+    /// Finishes an entry, no more values can be appended to it until it is started again.
+    ///
+    /// # Errors
+    /// - [`DataLogError::UnknownEntry`] if the entry has not been started.
+    /// - [`DataLogError::Io`] if the record could not be written.
+    pub fn finish_entry(&mut self, name: &str) -> Result<(), DataLogError> {
+        let (id, _) = self
+            .entries
+            .remove(name)
+            .ok_or_else(|| DataLogError::UnknownEntry(name.to_owned()))?;
+        let mut payload = vec![CONTROL_FINISH];
+        payload.extend_from_slice(&id.to_le_bytes());
+        self.write_record(0, &payload)
+    }
+
+    /// Replaces the metadata of a started entry.
+    ///
+    /// # Errors
+    /// - [`DataLogError::UnknownEntry`] if the entry has not been started.
+    /// - [`DataLogError::Io`] if the record could not be written.
+    pub fn set_metadata(&mut self, name: &str, metadata: &str) -> Result<(), DataLogError> {
+        let (id, _) = *self
+            .entries
+            .get(name)
+            .ok_or_else(|| DataLogError::UnknownEntry(name.to_owned()))?;
+        let mut payload = vec![CONTROL_SET_METADATA];
+        payload.extend_from_slice(&id.to_le_bytes());
+        push_str(&mut payload, metadata);
+        self.write_record(0, &payload)
+    }
+
+    /// Appends a value to the named entry, starting the entry if needed.
+    ///
+    /// # Errors
+    /// - [`DataLogError::TypeMismatch`] if the entry was started with a different type.
+    /// - [`DataLogError::Io`] if the record could not be written.
+    pub fn append(&mut self, name: &str, value: impl Into<LogValue>) -> Result<(), DataLogError> {
+        let value = value.into();
+        let id = self.start_entry(name, value.type_str(), "")?;
+        self.write_record(id, &value.encode())
+    }
+
+    /// Flushes any buffered records to the underlying writer.
+    ///
+    /// # Errors
+    /// - [`DataLogError::Io`] if the writer could not be flushed.
+    pub fn flush(&mut self) -> Result<(), DataLogError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+impl Drop for DataLog {
+    fn drop(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            tracing::warn!("Failed to flush data log: {err}");
+        }
+    }
+}
+
+/// Installs a data log for the current thread, returning the previously installed one.
+pub(crate) fn install(log: Option<DataLog>) -> Option<DataLog> {
+    DATA_LOG.with(|data_log| std::mem::replace(&mut *data_log.borrow_mut(), log))
+}
+
+/// Returns true if a data log is installed on the current thread.
+#[must_use]
+pub fn is_active() -> bool {
+    DATA_LOG.with(|data_log| data_log.borrow().is_some())
+}
+
+/// Runs `f` with the data log installed on the current thread.
+///
+/// # Errors
+/// - [`DataLogError::NoDataLog`] if no data log is installed on the current thread.
+/// - Any error returned by `f`.
+pub fn with_data_log<R>(
+    f: impl FnOnce(&mut DataLog) -> Result<R, DataLogError>,
+) -> Result<R, DataLogError> {
+    DATA_LOG.with(|data_log| match &mut *data_log.borrow_mut() {
+        Some(data_log) => f(data_log),
+        None => Err(DataLogError::NoDataLog),
+    })
+}
+
+/// Appends a value to the named entry of the data log installed on the current thread.
+/// Meant to be called from [`Subsystem::log`](crate::Subsystem::log).
+///
+/// # Errors
+/// - [`DataLogError::NoDataLog`] if no data log is installed on the current thread.
+/// - [`DataLogError::TypeMismatch`] if the entry was started with a different type.
+/// - [`DataLogError::Io`] if the record could not be written.
+pub fn append(name: &str, value: impl Into<LogValue>) -> Result<(), DataLogError> {
+    with_data_log(|data_log| data_log.append(name, value))
+}
+
+/// Flushes the data log installed on the current thread.
+///
+/// # Errors
+/// - [`DataLogError::NoDataLog`] if no data log is installed on the current thread.
+/// - [`DataLogError::Io`] if the writer could not be flushed.
+pub fn flush() -> Result<(), DataLogError> {
+    with_data_log(DataLog::flush)
+}
+
+/// Appends a value for the command manager, errors are reported through `tracing`.
+pub(crate) fn append_internal(name: &str, value: impl Into<LogValue>) {
+    if let Err(err) = append(name, value) {
+        if !matches!(err, DataLogError::NoDataLog) {
+            tracing::warn!("Failed to write {name} to data log: {err}");
+        }
+    }
+}